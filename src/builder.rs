@@ -0,0 +1,1089 @@
+//! Construction of proto files in code.
+//!
+//! [`Proto::builder`] returns a [`ProtoBuilder`] whose methods add definitions in the order they
+//! are called. Definitions with a body take a closure that receives a builder for that body and
+//! returns it once the body is complete, so nested definitions read like the proto file itself.
+//!
+//! The result is the same AST the parser produces for the equivalent source: well-known options
+//! such as `deprecated` or `json_name` end up in their typed properties, enum aliases are
+//! collected, declarations are recorded in order, and spans and comments are empty unless given.
+//! Everything the parser would reject is reported by [`ProtoBuilder::build`].
+//!
+//! # Examples
+//!
+//! ```
+//! use proto_file_parser::{Constant, Label, Proto};
+//!
+//! let proto = Proto::builder()
+//!     .syntax("proto3")
+//!     .package("acme.v1")
+//!     .message("User", |m| {
+//!         m.field("name", "string", 1)
+//!             .field_with("emails", "string", 2, |f| f.label(Label::Repeated))
+//!             .field_with("login", "string", 3, |f| f.option("deprecated", Constant::Bool(true)))
+//!     })
+//!     .service("Users", |s| s.method("Get", "User", "User"))
+//!     .build()
+//!     .unwrap();
+//!
+//! let parsed = Proto::parse(r#"
+//!     syntax = "proto3";
+//!     package acme.v1;
+//!     message User {
+//!         string name = 1;
+//!         repeated string emails = 2;
+//!         string login = 3 [deprecated = true];
+//!     }
+//!     service Users {
+//!         rpc Get (User) returns (User);
+//!     }
+//! "#).unwrap();
+//! assert!(proto.eq_ignoring_spans(&parsed));
+//! ```
+
+use crate::visit::{self, VisitMut};
+use crate::{
+    features, Comments, Constant, Declaration, DeclarationKind, EnumDef, EnumValue, Extend,
+    ExtensionRange, Field, FieldKind, Import, ImportModifier, Label, Message, Method, Oneof,
+    OptionDef, ParserError, Proto, Range, Service, Span, ENUM_NUMBERS, FIELD_NUMBERS,
+    MAP_KEY_TYPES,
+};
+use std::ops::RangeInclusive;
+
+impl Proto {
+    /// Starts building a proto3 file without a package.
+    pub fn builder() -> ProtoBuilder {
+        ProtoBuilder {
+            proto: Proto {
+                syntax: "proto3".to_string(),
+                edition: None,
+                package: None,
+                imports: Vec::new(),
+                messages: Vec::new(),
+                enums: Vec::new(),
+                services: Vec::new(),
+                extends: Vec::new(),
+                options: Vec::new(),
+                declarations: Vec::new(),
+                span: Span::default(),
+            },
+        }
+    }
+}
+
+/// Builds a proto file, see [`Proto::builder`].
+#[derive(Debug, Clone)]
+pub struct ProtoBuilder {
+    proto: Proto,
+}
+
+impl ProtoBuilder {
+    /// Sets the syntax of the file, `"proto2"` or `"proto3"`.
+    pub fn syntax(mut self, syntax: impl Into<String>) -> Self {
+        self.proto.syntax = syntax.into();
+        self.proto.edition = None;
+        self
+    }
+
+    /// Declares the edition of the file (e.g. `"2023"`) instead of a syntax.
+    pub fn edition(mut self, edition: impl Into<String>) -> Self {
+        self.proto.syntax = "editions".to_string();
+        self.proto.edition = Some(edition.into());
+        self
+    }
+
+    /// Sets the package of the file.
+    pub fn package(mut self, package: impl Into<String>) -> Self {
+        self.proto.package = Some(package.into());
+        self
+    }
+
+    /// Adds an import without a modifier.
+    pub fn import(self, path: impl Into<String>) -> Self {
+        self.push_import(path.into(), None)
+    }
+
+    /// Adds a `public` or `weak` import.
+    pub fn import_with(self, path: impl Into<String>, modifier: ImportModifier) -> Self {
+        self.push_import(path.into(), Some(modifier))
+    }
+
+    /// Adds a file-level option.
+    pub fn option(mut self, name: impl Into<String>, value: Constant) -> Self {
+        self.proto.options.push(new_option(name.into(), value));
+        self
+    }
+
+    /// Adds a message whose body is built by `build`.
+    pub fn message(
+        mut self,
+        name: impl Into<String>,
+        build: impl FnOnce(MessageBuilder) -> MessageBuilder,
+    ) -> Self {
+        let message = build(MessageBuilder::new(name.into())).message;
+        declare(
+            &mut self.proto.declarations,
+            DeclarationKind::Message,
+            self.proto.messages.len(),
+        );
+        self.proto.messages.push(message);
+        self
+    }
+
+    /// Adds an enum whose body is built by `build`.
+    pub fn enum_def(
+        mut self,
+        name: impl Into<String>,
+        build: impl FnOnce(EnumBuilder) -> EnumBuilder,
+    ) -> Self {
+        let enum_def = build(EnumBuilder::new(name.into())).enum_def;
+        declare(
+            &mut self.proto.declarations,
+            DeclarationKind::Enum,
+            self.proto.enums.len(),
+        );
+        self.proto.enums.push(enum_def);
+        self
+    }
+
+    /// Adds a service whose body is built by `build`.
+    pub fn service(
+        mut self,
+        name: impl Into<String>,
+        build: impl FnOnce(ServiceBuilder) -> ServiceBuilder,
+    ) -> Self {
+        let service = build(ServiceBuilder::new(name.into())).service;
+        declare(
+            &mut self.proto.declarations,
+            DeclarationKind::Service,
+            self.proto.services.len(),
+        );
+        self.proto.services.push(service);
+        self
+    }
+
+    /// Adds an extend block for the message `extendee`, with fields added by `build`.
+    pub fn extend(
+        mut self,
+        extendee: impl Into<String>,
+        build: impl FnOnce(ExtendBuilder) -> ExtendBuilder,
+    ) -> Self {
+        let extend = build(ExtendBuilder::new(extendee.into())).extend;
+        declare(
+            &mut self.proto.declarations,
+            DeclarationKind::Extend,
+            self.proto.extends.len(),
+        );
+        self.proto.extends.push(extend);
+        self
+    }
+
+    /// Validates the file and returns it.
+    ///
+    /// Fails with a `ValidationError` for anything the parser would reject: invalid names,
+    /// field numbers or ranges, map key types, group names, labels on oneof and map fields,
+    /// enum aliases without `allow_alias`, invalid option names, well-known options with values
+    /// of the wrong type, and unknown features. Constants must also be written as the parser
+    /// reads them back, so e.g. `Constant::Identifier("true")` is rejected in favor of
+    /// `Constant::Bool(true)`.
+    pub fn build(self) -> Result<Proto, ParserError> {
+        let mut proto = self.proto;
+
+        match (proto.syntax.as_str(), proto.edition.as_deref()) {
+            ("proto2" | "proto3", None) => {}
+            ("editions", Some(edition)) if features::SUPPORTED_EDITIONS.contains(&edition) => {}
+            ("editions", Some(edition)) => {
+                return Err(invalid(format!("unsupported edition \"{}\"", edition)));
+            }
+            (syntax, _) => return Err(invalid(format!("unsupported syntax \"{}\"", syntax))),
+        }
+        if let Some(package) = &proto.package {
+            if !is_full_ident(package) {
+                return Err(invalid(format!("invalid package name '{}'", package)));
+            }
+        }
+        let scope = proto.package.as_deref().unwrap_or_default();
+        check_options(&proto.options, scope)?;
+
+        let mut validator = Validator { error: None };
+        validator.visit_proto_mut(&mut proto);
+        if let Some(error) = validator.error {
+            return Err(error);
+        }
+
+        proto.resolve_features()?;
+
+        Ok(proto)
+    }
+
+    fn push_import(mut self, path: String, modifier: Option<ImportModifier>) -> Self {
+        self.proto.imports.push(Import {
+            path,
+            modifier,
+            comments: Comments::default(),
+            span: Span::default(),
+        });
+        self
+    }
+}
+
+/// Builds the body of a message, see [`ProtoBuilder::message`].
+#[derive(Debug, Clone)]
+pub struct MessageBuilder {
+    message: Message,
+}
+
+impl MessageBuilder {
+    fn new(name: String) -> Self {
+        MessageBuilder {
+            message: Message::new(name, Span::default()),
+        }
+    }
+
+    /// Adds a field without a label or options.
+    pub fn field(self, name: impl Into<String>, type_name: impl Into<String>, tag: i32) -> Self {
+        self.field_with(name, type_name, tag, |f| f)
+    }
+
+    /// Adds a field whose label, options and comments are set by `build`.
+    pub fn field_with(
+        mut self,
+        name: impl Into<String>,
+        type_name: impl Into<String>,
+        tag: i32,
+        build: impl FnOnce(FieldBuilder) -> FieldBuilder,
+    ) -> Self {
+        let field = new_field(name.into(), type_name.into(), tag, FieldKind::Normal);
+        self.push_field(build(FieldBuilder { field }).field);
+        self
+    }
+
+    /// Adds a map field (`map<key_type, value_type>`).
+    pub fn map_field(
+        self,
+        name: impl Into<String>,
+        key_type: impl Into<String>,
+        value_type: impl Into<String>,
+        tag: i32,
+    ) -> Self {
+        self.map_field_with(name, key_type, value_type, tag, |f| f)
+    }
+
+    /// Adds a map field whose options and comments are set by `build`.
+    pub fn map_field_with(
+        mut self,
+        name: impl Into<String>,
+        key_type: impl Into<String>,
+        value_type: impl Into<String>,
+        tag: i32,
+        build: impl FnOnce(FieldBuilder) -> FieldBuilder,
+    ) -> Self {
+        let (key_type, value_type) = (key_type.into(), value_type.into());
+        let mut field = new_field(
+            name.into(),
+            format!("map<{}, {}>", key_type, value_type),
+            tag,
+            FieldKind::Map {
+                key_type,
+                value_type,
+            },
+        );
+        field.label = Label::Repeated;
+        self.push_field(build(FieldBuilder { field }).field);
+        self
+    }
+
+    /// Adds a proto2 group: a field named after the lowercased group name and
+    /// a nested message whose body is built by `build`.
+    pub fn group(
+        mut self,
+        label: Label,
+        name: impl Into<String>,
+        tag: i32,
+        build: impl FnOnce(MessageBuilder) -> MessageBuilder,
+    ) -> Self {
+        let name = name.into();
+        let mut field = new_field(name.to_lowercase(), name.clone(), tag, FieldKind::Group);
+        field.label = label;
+        self.push_field(field);
+        self.push_message(build(MessageBuilder::new(name)).message);
+        self
+    }
+
+    /// Adds a oneof whose fields are added by `build`.
+    pub fn oneof(
+        mut self,
+        name: impl Into<String>,
+        build: impl FnOnce(OneofBuilder) -> OneofBuilder,
+    ) -> Self {
+        let oneof = build(OneofBuilder::new(name.into())).oneof;
+        declare(
+            &mut self.message.declarations,
+            DeclarationKind::Oneof,
+            self.message.oneofs.len(),
+        );
+        self.message.oneofs.push(oneof);
+        self
+    }
+
+    /// Adds a nested message whose body is built by `build`.
+    pub fn message(
+        mut self,
+        name: impl Into<String>,
+        build: impl FnOnce(MessageBuilder) -> MessageBuilder,
+    ) -> Self {
+        self.push_message(build(MessageBuilder::new(name.into())).message);
+        self
+    }
+
+    /// Adds a nested enum whose body is built by `build`.
+    pub fn enum_def(
+        mut self,
+        name: impl Into<String>,
+        build: impl FnOnce(EnumBuilder) -> EnumBuilder,
+    ) -> Self {
+        let enum_def = build(EnumBuilder::new(name.into())).enum_def;
+        let index = self.message.nested_enums.len();
+        declare(&mut self.message.declarations, DeclarationKind::Enum, index);
+        self.message.nested_enums.push(enum_def);
+        self
+    }
+
+    /// Adds an extend block for the message `extendee`, with fields added by `build`.
+    pub fn extend(
+        mut self,
+        extendee: impl Into<String>,
+        build: impl FnOnce(ExtendBuilder) -> ExtendBuilder,
+    ) -> Self {
+        let extend = build(ExtendBuilder::new(extendee.into())).extend;
+        declare(
+            &mut self.message.declarations,
+            DeclarationKind::Extend,
+            self.message.extends.len(),
+        );
+        self.message.extends.push(extend);
+        self
+    }
+
+    /// Reserves the field numbers from `start` to `end`, both inclusive.
+    pub fn reserved_range(mut self, start: i32, end: i32) -> Self {
+        self.message.reserved_ranges.push(new_range(start, end));
+        self
+    }
+
+    /// Reserves a field name.
+    pub fn reserved_name(mut self, name: impl Into<String>) -> Self {
+        self.message.reserved_names.push(name.into());
+        self
+    }
+
+    /// Makes the field numbers from `start` to `end`, both inclusive, available to extensions.
+    pub fn extension_range(mut self, start: i32, end: i32) -> Self {
+        self.message.extension_ranges.push(ExtensionRange {
+            start,
+            end,
+            options: Vec::new(),
+            span: Span::default(),
+        });
+        self
+    }
+
+    /// Adds a message-level option.
+    pub fn option(mut self, name: impl Into<String>, value: Constant) -> Self {
+        self.message.options.push(new_option(name.into(), value));
+        self
+    }
+
+    /// Sets the comments attached to the message.
+    pub fn comments(mut self, comments: Comments) -> Self {
+        self.message.comments = comments;
+        self
+    }
+
+    fn push_field(&mut self, field: Field) {
+        declare(
+            &mut self.message.declarations,
+            DeclarationKind::Field,
+            self.message.fields.len(),
+        );
+        self.message.fields.push(field);
+    }
+
+    fn push_message(&mut self, message: Message) {
+        let index = self.message.nested_messages.len();
+        declare(
+            &mut self.message.declarations,
+            DeclarationKind::Message,
+            index,
+        );
+        self.message.nested_messages.push(message);
+    }
+}
+
+/// Sets the label, options and comments of a field, see [`MessageBuilder::field_with`].
+#[derive(Debug, Clone)]
+pub struct FieldBuilder {
+    field: Field,
+}
+
+impl FieldBuilder {
+    /// Sets the label of the field.
+    ///
+    /// Fields of oneofs and map fields cannot have a label, which [`ProtoBuilder::build`] reports.
+    pub fn label(mut self, label: Label) -> Self {
+        self.field.label = label;
+        self
+    }
+
+    /// Adds an option; well-known options like `deprecated` are stored in their typed properties.
+    pub fn option(mut self, name: impl Into<String>, value: Constant) -> Self {
+        self.field.options.push(new_option(name.into(), value));
+        self
+    }
+
+    /// Sets the comments attached to the field.
+    pub fn comments(mut self, comments: Comments) -> Self {
+        self.field.comments = comments;
+        self
+    }
+}
+
+/// Builds the fields of a oneof, see [`MessageBuilder::oneof`].
+#[derive(Debug, Clone)]
+pub struct OneofBuilder {
+    oneof: Oneof,
+}
+
+impl OneofBuilder {
+    fn new(name: String) -> Self {
+        OneofBuilder {
+            oneof: Oneof {
+                name,
+                fields: Vec::new(),
+                options: Vec::new(),
+                comments: Comments::default(),
+                span: Span::default(),
+            },
+        }
+    }
+
+    /// Adds a field without options.
+    pub fn field(self, name: impl Into<String>, type_name: impl Into<String>, tag: i32) -> Self {
+        self.field_with(name, type_name, tag, |f| f)
+    }
+
+    /// Adds a field whose options and comments are set by `build`.
+    pub fn field_with(
+        mut self,
+        name: impl Into<String>,
+        type_name: impl Into<String>,
+        tag: i32,
+        build: impl FnOnce(FieldBuilder) -> FieldBuilder,
+    ) -> Self {
+        let field = new_field(name.into(), type_name.into(), tag, FieldKind::Normal);
+        self.oneof.fields.push(build(FieldBuilder { field }).field);
+        self
+    }
+
+    /// Adds a oneof-level option.
+    pub fn option(mut self, name: impl Into<String>, value: Constant) -> Self {
+        self.oneof.options.push(new_option(name.into(), value));
+        self
+    }
+
+    /// Sets the comments attached to the oneof.
+    pub fn comments(mut self, comments: Comments) -> Self {
+        self.oneof.comments = comments;
+        self
+    }
+}
+
+/// Builds the body of an enum, see [`ProtoBuilder::enum_def`].
+#[derive(Debug, Clone)]
+pub struct EnumBuilder {
+    enum_def: EnumDef,
+}
+
+impl EnumBuilder {
+    fn new(name: String) -> Self {
+        EnumBuilder {
+            enum_def: EnumDef {
+                name,
+                values: Vec::new(),
+                reserved_ranges: Vec::new(),
+                reserved_names: Vec::new(),
+                options: Vec::new(),
+                aliases: Vec::new(),
+                comments: Comments::default(),
+                span: Span::default(),
+            },
+        }
+    }
+
+    /// Adds a value without options.
+    pub fn value(self, name: impl Into<String>, number: i32) -> Self {
+        self.value_with(name, number, |v| v)
+    }
+
+    /// Adds a value whose options and comments are set by `build`.
+    pub fn value_with(
+        mut self,
+        name: impl Into<String>,
+        number: i32,
+        build: impl FnOnce(EnumValueBuilder) -> EnumValueBuilder,
+    ) -> Self {
+        let value = EnumValue {
+            name: name.into(),
+            number,
+            deprecated: None,
+            debug_redact: None,
+            options: Vec::new(),
+            comments: Comments::default(),
+            span: Span::default(),
+        };
+        self.enum_def
+            .values
+            .push(build(EnumValueBuilder { value }).value);
+        self
+    }
+
+    /// Reserves the value numbers from `start` to `end`, both inclusive.
+    pub fn reserved_range(mut self, start: i32, end: i32) -> Self {
+        self.enum_def.reserved_ranges.push(new_range(start, end));
+        self
+    }
+
+    /// Reserves a value name.
+    pub fn reserved_name(mut self, name: impl Into<String>) -> Self {
+        self.enum_def.reserved_names.push(name.into());
+        self
+    }
+
+    /// Adds an enum-level option.
+    pub fn option(mut self, name: impl Into<String>, value: Constant) -> Self {
+        self.enum_def.options.push(new_option(name.into(), value));
+        self
+    }
+
+    /// Sets the comments attached to the enum.
+    pub fn comments(mut self, comments: Comments) -> Self {
+        self.enum_def.comments = comments;
+        self
+    }
+}
+
+/// Sets the options and comments of an enum value, see [`EnumBuilder::value_with`].
+#[derive(Debug, Clone)]
+pub struct EnumValueBuilder {
+    value: EnumValue,
+}
+
+impl EnumValueBuilder {
+    /// Adds an option; well-known options like `deprecated` are stored in their typed properties.
+    pub fn option(mut self, name: impl Into<String>, value: Constant) -> Self {
+        self.value.options.push(new_option(name.into(), value));
+        self
+    }
+
+    /// Sets the comments attached to the value.
+    pub fn comments(mut self, comments: Comments) -> Self {
+        self.value.comments = comments;
+        self
+    }
+}
+
+/// Builds the body of a service, see [`ProtoBuilder::service`].
+#[derive(Debug, Clone)]
+pub struct ServiceBuilder {
+    service: Service,
+}
+
+impl ServiceBuilder {
+    fn new(name: String) -> Self {
+        ServiceBuilder {
+            service: Service {
+                name,
+                methods: Vec::new(),
+                options: Vec::new(),
+                comments: Comments::default(),
+                span: Span::default(),
+            },
+        }
+    }
+
+    /// Adds a unary method.
+    pub fn method(
+        self,
+        name: impl Into<String>,
+        input_type: impl Into<String>,
+        output_type: impl Into<String>,
+    ) -> Self {
+        self.method_with(name, input_type, output_type, |m| m)
+    }
+
+    /// Adds a method whose streaming, options and comments are set by `build`.
+    pub fn method_with(
+        mut self,
+        name: impl Into<String>,
+        input_type: impl Into<String>,
+        output_type: impl Into<String>,
+        build: impl FnOnce(MethodBuilder) -> MethodBuilder,
+    ) -> Self {
+        let method = Method {
+            name: name.into(),
+            input_type: input_type.into(),
+            output_type: output_type.into(),
+            client_streaming: false,
+            server_streaming: false,
+            options: Vec::new(),
+            comments: Comments::default(),
+            span: Span::default(),
+        };
+        self.service
+            .methods
+            .push(build(MethodBuilder { method }).method);
+        self
+    }
+
+    /// Adds a service-level option.
+    pub fn option(mut self, name: impl Into<String>, value: Constant) -> Self {
+        self.service.options.push(new_option(name.into(), value));
+        self
+    }
+
+    /// Sets the comments attached to the service.
+    pub fn comments(mut self, comments: Comments) -> Self {
+        self.service.comments = comments;
+        self
+    }
+}
+
+/// Sets the streaming, options and comments of a method, see [`ServiceBuilder::method_with`].
+#[derive(Debug, Clone)]
+pub struct MethodBuilder {
+    method: Method,
+}
+
+impl MethodBuilder {
+    /// Makes the client send a stream of input messages.
+    pub fn client_streaming(mut self) -> Self {
+        self.method.client_streaming = true;
+        self
+    }
+
+    /// Makes the server send a stream of output messages.
+    pub fn server_streaming(mut self) -> Self {
+        self.method.server_streaming = true;
+        self
+    }
+
+    /// Adds a method option.
+    pub fn option(mut self, name: impl Into<String>, value: Constant) -> Self {
+        self.method.options.push(new_option(name.into(), value));
+        self
+    }
+
+    /// Sets the comments attached to the method.
+    pub fn comments(mut self, comments: Comments) -> Self {
+        self.method.comments = comments;
+        self
+    }
+}
+
+/// Builds the fields of an extend block, see [`ProtoBuilder::extend`].
+#[derive(Debug, Clone)]
+pub struct ExtendBuilder {
+    extend: Extend,
+}
+
+impl ExtendBuilder {
+    fn new(extendee: String) -> Self {
+        ExtendBuilder {
+            extend: Extend {
+                extendee,
+                fields: Vec::new(),
+                scope: String::new(),
+                comments: Comments::default(),
+                span: Span::default(),
+            },
+        }
+    }
+
+    /// Adds an extension field without a label or options.
+    pub fn field(self, name: impl Into<String>, type_name: impl Into<String>, tag: i32) -> Self {
+        self.field_with(name, type_name, tag, |f| f)
+    }
+
+    /// Adds an extension field whose label, options and comments are set by `build`.
+    pub fn field_with(
+        mut self,
+        name: impl Into<String>,
+        type_name: impl Into<String>,
+        tag: i32,
+        build: impl FnOnce(FieldBuilder) -> FieldBuilder,
+    ) -> Self {
+        let field = new_field(name.into(), type_name.into(), tag, FieldKind::Normal);
+        self.extend.fields.push(build(FieldBuilder { field }).field);
+        self
+    }
+
+    /// Sets the comments attached to the extend block.
+    pub fn comments(mut self, comments: Comments) -> Self {
+        self.extend.comments = comments;
+        self
+    }
+}
+
+/// Records the declaration of the definition that is about to be added at `index`.
+fn declare(declarations: &mut Vec<Declaration>, kind: DeclarationKind, index: usize) {
+    declarations.push(Declaration { kind, index });
+}
+
+fn new_field(name: String, type_name: String, tag: i32, kind: FieldKind) -> Field {
+    Field {
+        name,
+        type_name,
+        tag,
+        label: Label::Implicit,
+        kind,
+        deprecated: None,
+        packed: None,
+        json_name: None,
+        default: None,
+        lazy: None,
+        debug_redact: None,
+        options: Vec::new(),
+        comments: Comments::default(),
+        span: Span::default(),
+    }
+}
+
+fn new_option(name: String, value: Constant) -> OptionDef {
+    OptionDef {
+        name,
+        value,
+        comments: Comments::default(),
+        span: Span::default(),
+    }
+}
+
+fn new_range(start: i32, end: i32) -> Range {
+    Range {
+        start,
+        end,
+        span: Span::default(),
+    }
+}
+
+fn invalid(message: String) -> ParserError {
+    ParserError::ValidationError(message)
+}
+
+/// Indicates if a name is a valid identifier (e.g. `User`).
+pub(crate) fn is_ident(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Indicates if a name is a valid dotted identifier (e.g. `acme.v1`).
+fn is_full_ident(name: &str) -> bool {
+    name.split('.').all(is_ident)
+}
+
+/// Indicates if a name is a valid type reference (e.g. `User` or `.acme.v1.User`).
+fn is_type_ref(name: &str) -> bool {
+    is_full_ident(name.strip_prefix('.').unwrap_or(name))
+}
+
+/// Indicates if a name is a valid option name (e.g. `deprecated` or `(acme.v1.opt).sub`).
+fn is_option_name(name: &str) -> bool {
+    let mut rest = name;
+    loop {
+        let end = match rest.strip_prefix('(') {
+            Some(inner) => match inner.find(')') {
+                Some(close) => close + 2,
+                None => return false,
+            },
+            None => rest.find('.').unwrap_or(rest.len()),
+        };
+        let (part, tail) = rest.split_at(end);
+        let valid = match part.strip_prefix('(').and_then(|p| p.strip_suffix(')')) {
+            Some(extension) => is_type_ref(extension),
+            None => is_ident(part),
+        };
+        if !valid {
+            return false;
+        }
+        match tail.strip_prefix('.') {
+            Some(next) => rest = next,
+            None => return tail.is_empty(),
+        }
+    }
+}
+
+/// Checks every definition of a built file and brings it into the shape the parser produces.
+///
+/// Only the first error is kept, since the visitor callbacks cannot fail.
+struct Validator {
+    error: Option<ParserError>,
+}
+
+impl Validator {
+    /// Runs a check unless an earlier one failed, keeping its error.
+    fn check(&mut self, check: impl FnOnce() -> Result<(), ParserError>) {
+        if self.error.is_none() {
+            self.error = check().err();
+        }
+    }
+}
+
+impl VisitMut for Validator {
+    fn visit_message_mut(&mut self, message: &mut Message, scope: &str) {
+        self.check(|| {
+            let name = Proto::qualify(scope, &message.name);
+            check_name(&message.name, "message", scope)?;
+            for range in &message.reserved_ranges {
+                check_range(range.start, range.end, &FIELD_NUMBERS, &name)?;
+            }
+            for range in &message.extension_ranges {
+                check_range(range.start, range.end, &FIELD_NUMBERS, &name)?;
+                check_options(&range.options, &name)?;
+            }
+            check_options(&message.options, &name)
+        });
+        visit::walk_message_mut(self, message, scope);
+    }
+
+    fn visit_field_mut(&mut self, field: &mut Field, scope: &str) {
+        self.check(|| {
+            check_name(&field.name, "field", scope)?;
+            let name = Proto::qualify(scope, &field.name);
+            check_range(field.tag, field.tag, &FIELD_NUMBERS, &name)?;
+
+            match &field.kind {
+                FieldKind::Normal => check_type(&field.type_name, &name)?,
+                FieldKind::Map {
+                    key_type,
+                    value_type,
+                } => {
+                    if field.label != Label::Repeated {
+                        return Err(invalid(format!("map field '{}' cannot have a label", name)));
+                    }
+                    if !MAP_KEY_TYPES.contains(&key_type.as_str()) {
+                        return Err(invalid(format!(
+                            "invalid map key type '{}' of '{}', \
+                             expected an integral type, bool or string",
+                            key_type, name
+                        )));
+                    }
+                    check_type(value_type, &name)?;
+                }
+                FieldKind::Group => {
+                    if !field
+                        .type_name
+                        .starts_with(|c: char| c.is_ascii_uppercase())
+                    {
+                        return Err(invalid(format!(
+                            "group name '{}' must start with a capital letter",
+                            field.type_name
+                        )));
+                    }
+                }
+            }
+
+            check_options(&field.options, &name)?;
+            for option in std::mem::take(&mut field.options) {
+                Proto::apply_field_option(field, option)
+                    .map_err(|message| invalid(format!("{} in '{}'", message, name)))?;
+            }
+            Ok(())
+        });
+    }
+
+    fn visit_oneof_mut(&mut self, oneof: &mut Oneof, scope: &str) {
+        self.check(|| {
+            check_name(&oneof.name, "oneof", scope)?;
+            let name = Proto::qualify(scope, &oneof.name);
+            for field in &oneof.fields {
+                if field.kind == FieldKind::Normal && field.label != Label::Implicit {
+                    return Err(invalid(format!(
+                        "field '{}' of oneof '{}' cannot have a label",
+                        field.name, name
+                    )));
+                }
+            }
+            check_options(&oneof.options, &name)
+        });
+        visit::walk_oneof_mut(self, oneof, scope);
+    }
+
+    fn visit_enum_mut(&mut self, enum_def: &mut EnumDef, scope: &str) {
+        self.check(|| {
+            check_name(&enum_def.name, "enum", scope)?;
+            let name = Proto::qualify(scope, &enum_def.name);
+            for range in &enum_def.reserved_ranges {
+                check_range(range.start, range.end, &ENUM_NUMBERS, &name)?;
+            }
+            check_options(&enum_def.options, &name)?;
+            enum_def.aliases = Proto::collect_aliases(enum_def)?;
+            Ok(())
+        });
+        visit::walk_enum_mut(self, enum_def, scope);
+    }
+
+    fn visit_enum_value_mut(&mut self, value: &mut EnumValue, scope: &str) {
+        self.check(|| {
+            check_name(&value.name, "enum value", scope)?;
+            let name = Proto::qualify(scope, &value.name);
+            check_options(&value.options, &name)?;
+            for option in std::mem::take(&mut value.options) {
+                Proto::apply_enum_value_option(value, option)
+                    .map_err(|message| invalid(format!("{} in '{}'", message, name)))?;
+            }
+            Ok(())
+        });
+    }
+
+    fn visit_service_mut(&mut self, service: &mut Service, scope: &str) {
+        self.check(|| {
+            check_name(&service.name, "service", scope)?;
+            check_options(&service.options, &Proto::qualify(scope, &service.name))
+        });
+        visit::walk_service_mut(self, service, scope);
+    }
+
+    fn visit_method_mut(&mut self, method: &mut Method, scope: &str) {
+        self.check(|| {
+            check_name(&method.name, "method", scope)?;
+            let name = Proto::qualify(scope, &method.name);
+            check_type(&method.input_type, &name)?;
+            check_type(&method.output_type, &name)?;
+            check_options(&method.options, &name)
+        });
+    }
+
+    fn visit_extend_mut(&mut self, extend: &mut Extend, scope: &str) {
+        self.check(|| {
+            extend.scope = scope.to_string();
+            if is_type_ref(&extend.extendee) {
+                Ok(())
+            } else {
+                Err(invalid(format!(
+                    "invalid extendee name '{}'",
+                    extend.extendee
+                )))
+            }
+        });
+        visit::walk_extend_mut(self, extend, scope);
+    }
+}
+
+/// Checks that the name of a definition declared in `scope` is an identifier.
+fn check_name(name: &str, kind: &str, scope: &str) -> Result<(), ParserError> {
+    if is_ident(name) {
+        Ok(())
+    } else if scope.is_empty() {
+        Err(invalid(format!("invalid {} name '{}'", kind, name)))
+    } else {
+        Err(invalid(format!(
+            "invalid {} name '{}' in '{}'",
+            kind, name, scope
+        )))
+    }
+}
+
+/// Checks that a type referenced by the definition `owner` is a valid type reference.
+fn check_type(type_name: &str, owner: &str) -> Result<(), ParserError> {
+    if is_type_ref(type_name) {
+        Ok(())
+    } else {
+        Err(invalid(format!(
+            "invalid type name '{}' in '{}'",
+            type_name, owner
+        )))
+    }
+}
+
+/// Checks that the options of the definition `owner` have valid names, and values that
+/// the parser reads back as the same constants.
+fn check_options(options: &[OptionDef], owner: &str) -> Result<(), ParserError> {
+    let location = |option: &OptionDef| match owner {
+        "" => format!("option '{}'", option.name),
+        _ => format!("option '{}' of '{}'", option.name, owner),
+    };
+    for option in options {
+        if !is_option_name(&option.name) {
+            return Err(invalid(format!("invalid name of {}", location(option))));
+        }
+        check_constant(&option.value)
+            .map_err(|problem| invalid(format!("{} in {}", problem, location(option))))?;
+    }
+    Ok(())
+}
+
+/// Checks that a constant is written as itself, describing the problem if it is not.
+///
+/// Lists are only allowed as values of aggregate fields.
+fn check_constant(value: &Constant) -> Result<(), String> {
+    match value {
+        Constant::Identifier(name)
+            if !is_full_ident(name)
+                || matches!(name.as_str(), "true" | "false")
+                || matches!(name.split('.').next(), Some("inf" | "nan")) =>
+        {
+            Err(format!("invalid identifier '{}'", name))
+        }
+        Constant::UnsignedInteger(value) if i64::try_from(*value).is_ok() => Err(format!(
+            "unsigned integer {} fits into a signed integer",
+            value
+        )),
+        Constant::Bytes(bytes) if std::str::from_utf8(bytes).is_ok() => {
+            Err("bytes that are valid UTF-8 must be a string".to_string())
+        }
+        Constant::Aggregate(fields) => {
+            for field in fields {
+                let name = field
+                    .name
+                    .strip_prefix('[')
+                    .and_then(|n| n.strip_suffix(']'));
+                if !name.map_or(is_ident(&field.name), is_full_ident) {
+                    return Err(format!("invalid aggregate field name '{}'", field.name));
+                }
+                match &field.value {
+                    Constant::List(values) => values.iter().try_for_each(check_constant)?,
+                    value => check_constant(value)?,
+                }
+            }
+            Ok(())
+        }
+        Constant::List(_) => {
+            Err("a list is only allowed as the value of an aggregate field".to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Checks that a number range of the definition `owner` lies within `bounds`.
+fn check_range(
+    start: i32,
+    end: i32,
+    bounds: &RangeInclusive<i32>,
+    owner: &str,
+) -> Result<(), ParserError> {
+    if let Some(number) = [start, end].into_iter().find(|n| !bounds.contains(n)) {
+        return Err(invalid(format!(
+            "number {} of '{}' is out of range, expected {} to {}",
+            number,
+            owner,
+            bounds.start(),
+            bounds.end()
+        )));
+    }
+    if end < start {
+        return Err(invalid(format!(
+            "range end {} of '{}' is smaller than range start {}",
+            end, owner, start
+        )));
+    }
+    Ok(())
+}
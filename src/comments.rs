@@ -0,0 +1,330 @@
+//! Comments attached to definitions, following the rules protoc uses for `SourceCodeInfo`.
+
+use crate::Rule;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Represents the comments attached to a definition.
+///
+/// A comment block directly before a definition is its leading comment. A comment on the same
+/// line after a definition is its trailing comment, as is a block on the next line that is not
+/// directly followed by another definition; for definitions with a body the trailing comment
+/// follows the opening brace. Blocks before the leading comment that are separated by blank
+/// lines are leading detached comments.
+///
+/// Comment markers are removed, so `// Hello` becomes `" Hello\n"`, and for block comments the
+/// leading whitespace and `*` of every line but the first are removed as well.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Comments {
+    /// Comment block directly before the definition
+    pub leading: Option<String>,
+    /// Comment directly after the definition, or after the opening brace of its body
+    pub trailing: Option<String>,
+    /// Comment blocks before the leading comment, separated by blank lines
+    pub leading_detached: Vec<String>,
+}
+
+impl Comments {
+    /// Indicates if no comments are attached.
+    pub fn is_empty(&self) -> bool {
+        self.leading.is_none() && self.trailing.is_none() && self.leading_detached.is_empty()
+    }
+}
+
+/// Collects the comments of all statements in a parsed file.
+///
+/// The result is keyed by the byte offset at which each commented statement starts.
+pub(crate) fn collect(
+    input: &str,
+    file: &pest::iterators::Pair<Rule>,
+) -> BTreeMap<usize, Comments> {
+    let mut collector = Collector {
+        input,
+        comments: scan(input),
+        attached: BTreeMap::new(),
+    };
+    collector.scope(file, None);
+    collector.attached
+}
+
+/// A comment in the source, as a byte range including its markers.
+struct RawComment {
+    start: usize,
+    end: usize,
+}
+
+impl RawComment {
+    fn is_line_comment(&self, input: &str) -> bool {
+        input[self.start..].starts_with("//")
+    }
+}
+
+/// A group of comments that protoc treats as one: either consecutive line comments
+/// or a single block comment.
+struct Block {
+    start: usize,
+    end: usize,
+    text: String,
+}
+
+/// Finds all comments in the input, skipping over string literals.
+///
+/// The comments are returned in source order.
+fn scan(input: &str) -> Vec<RawComment> {
+    let bytes = input.as_bytes();
+    let mut comments = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match (bytes[i], bytes.get(i + 1)) {
+            (b'"' | b'\'', _) => {
+                let quote = bytes[i];
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote && bytes[i] != b'\n' {
+                    if bytes[i] == b'\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                i += 1;
+            }
+            (b'/', Some(b'/')) => {
+                let start = i;
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+                comments.push(RawComment { start, end: i });
+            }
+            (b'/', Some(b'*')) => {
+                let start = i;
+                i += 2;
+                while i < bytes.len() && !bytes[i..].starts_with(b"*/") {
+                    i += 1;
+                }
+                i = (i + 2).min(bytes.len());
+                comments.push(RawComment { start, end: i });
+            }
+            _ => i += 1,
+        }
+    }
+
+    comments
+}
+
+/// Indicates if a rule is a statement that can have comments attached.
+fn is_statement(rule: Rule) -> bool {
+    matches!(
+        rule,
+        Rule::syntax
+            | Rule::edition
+            | Rule::package
+            | Rule::import
+            | Rule::option_stmt
+            | Rule::message_def
+            | Rule::enum_def
+            | Rule::service_def
+            | Rule::extend_def
+            | Rule::field
+            | Rule::map_field
+            | Rule::group
+            | Rule::oneof_def
+            | Rule::oneof_field
+            | Rule::reserved
+            | Rule::extensions
+            | Rule::enum_value
+            | Rule::rpc_def
+    )
+}
+
+/// Indicates if a statement may have a body in braces.
+fn has_body(rule: Rule) -> bool {
+    matches!(
+        rule,
+        Rule::message_def
+            | Rule::enum_def
+            | Rule::service_def
+            | Rule::extend_def
+            | Rule::group
+            | Rule::oneof_def
+            | Rule::rpc_def
+    )
+}
+
+struct Collector<'i> {
+    input: &'i str,
+    comments: Vec<RawComment>,
+    attached: BTreeMap<usize, Comments>,
+}
+
+impl Collector<'_> {
+    /// Attaches the comments inside a file or the body of a statement.
+    ///
+    /// Returns false if the statement turns out to have no body (e.g. `rpc Foo(A) returns (B);`).
+    fn scope(&mut self, pair: &pest::iterators::Pair<Rule>, owner: Option<usize>) -> bool {
+        let children: Vec<_> = pair.clone().into_inner().collect();
+
+        // The token before the first statement: the opening brace, or nothing at the start of a file
+        let (mut prev, end) = match owner {
+            Some(owner) => {
+                let header_end = children
+                    .iter()
+                    .filter(|child| !is_statement(child.as_rule()))
+                    .map(|child| child.as_span().end())
+                    .max()
+                    .unwrap_or(pair.as_span().start());
+                let mut open = self.next_token(header_end);
+                // The header of an rpc ends with the parenthesis around its response type
+                if self.input[open..].starts_with(')') {
+                    open = self.next_token(open + 1);
+                }
+                if self.input[open..].starts_with('{') {
+                    (Some((open + 1, Some(owner))), pair.as_span().end() - 1)
+                } else {
+                    return false;
+                }
+            }
+            None => (None, self.input.len()),
+        };
+
+        for statement in children
+            .iter()
+            .filter(|child| is_statement(child.as_rule()))
+        {
+            let start = statement.as_span().start();
+            self.gap(prev, start, Some(start));
+
+            // Comments after the closing brace of a body are not attached to anything
+            let body = has_body(statement.as_rule()) && self.scope(statement, Some(start));
+            prev = Some((statement.as_span().end(), (!body).then_some(start)));
+        }
+        self.gap(prev, end, None);
+
+        true
+    }
+
+    /// Attaches the comments between two tokens.
+    ///
+    /// `prev` is the end of the previous token together with the statement that may receive
+    /// a trailing comment, and `next` is the statement starting at `end`, if any.
+    fn gap(&mut self, prev: Option<(usize, Option<usize>)>, end: usize, next: Option<usize>) {
+        let start = prev.map_or(0, |(prev_end, _)| prev_end);
+        let first = self
+            .comments
+            .partition_point(|comment| comment.start < start);
+        let comments: Vec<&RawComment> = self.comments[first..]
+            .iter()
+            .take_while(|comment| comment.end <= end)
+            .collect();
+        let mut comments = &comments[..];
+        let mut trailing_owner = prev.and_then(|(_, owner)| owner);
+
+        // A comment on the same line as the previous token always belongs to it
+        if let (Some((prev_end, owner)), Some(first)) = (prev, comments.first()) {
+            if !self.input[prev_end..first.start].contains('\n') {
+                if let Some(owner) = owner {
+                    self.attached.entry(owner).or_default().trailing =
+                        Some(self.block(&comments[..1]).text);
+                }
+                comments = &comments[1..];
+                trailing_owner = None;
+            }
+        }
+
+        let mut blocks = self.blocks(comments);
+
+        // A block on the next line is trailing unless the next statement directly follows it
+        if let (Some(owner), Some(first)) = (trailing_owner, blocks.first()) {
+            if !self.has_blank_line(start, first.start)
+                && (blocks.len() > 1 || next.is_none() || self.has_blank_line(first.end, end))
+            {
+                let first = blocks.remove(0);
+                self.attached.entry(owner).or_default().trailing = Some(first.text);
+            }
+        }
+
+        let Some(next) = next else {
+            return;
+        };
+        let leading = match blocks.last() {
+            Some(last) if !self.has_blank_line(last.end, end) => blocks.pop().map(|b| b.text),
+            _ => None,
+        };
+        if leading.is_none() && blocks.is_empty() {
+            return;
+        }
+
+        let comments = self.attached.entry(next).or_default();
+        comments.leading = leading;
+        comments.leading_detached = blocks.into_iter().map(|block| block.text).collect();
+    }
+
+    /// Groups comments into blocks of consecutive line comments and single block comments.
+    fn blocks(&self, comments: &[&RawComment]) -> Vec<Block> {
+        let mut blocks = Vec::new();
+        let mut first = 0;
+
+        for i in 1..=comments.len() {
+            let joined = i < comments.len()
+                && comments[i - 1].is_line_comment(self.input)
+                && comments[i].is_line_comment(self.input)
+                && !self.has_blank_line(comments[i - 1].end, comments[i].start);
+            if !joined {
+                blocks.push(self.block(&comments[first..i]));
+                first = i;
+            }
+        }
+
+        blocks
+    }
+
+    /// Builds a block from comments, removing their markers.
+    fn block(&self, comments: &[&RawComment]) -> Block {
+        let mut text = String::new();
+
+        for comment in comments {
+            let raw = &self.input[comment.start..comment.end];
+            if let Some(line) = raw.strip_prefix("//") {
+                text.push_str(line.trim_end_matches('\r'));
+                text.push('\n');
+            } else {
+                let inner = &raw[2..raw.len() - 2];
+                for (i, line) in inner.split('\n').enumerate() {
+                    if i > 0 {
+                        text.push('\n');
+                        let line = line.trim_start();
+                        text.push_str(line.strip_prefix('*').unwrap_or(line));
+                    } else {
+                        text.push_str(line);
+                    }
+                }
+            }
+        }
+
+        Block {
+            start: comments[0].start,
+            end: comments[comments.len() - 1].end,
+            text,
+        }
+    }
+
+    /// Returns the position of the first token at or after `pos`, skipping whitespace and comments.
+    fn next_token(&self, mut pos: usize) -> usize {
+        loop {
+            while self.input[pos..].starts_with(|c: char| c.is_ascii_whitespace()) {
+                pos += 1;
+            }
+            match self
+                .comments
+                .binary_search_by_key(&pos, |comment| comment.start)
+            {
+                Ok(index) => pos = self.comments[index].end,
+                Err(_) => return pos,
+            }
+        }
+    }
+
+    /// Indicates if there is an empty line between two positions separated only by whitespace.
+    fn has_blank_line(&self, start: usize, end: usize) -> bool {
+        self.input[start..end].matches('\n').count() > 1
+    }
+}
@@ -0,0 +1,386 @@
+//! Protobuf Editions feature sets and their resolution.
+//!
+//! Every definition in a proto file has an effective set of features. Features declared with
+//! `option features.<name> = <VALUE>;` override the ones inherited from the enclosing scope,
+//! starting from the defaults of the file's edition (or of its proto2/proto3 syntax).
+
+use crate::{
+    Constant, EnumDef, Field, FieldKind, Label, Message, OptionDef, ParserError, Proto, Service,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Editions whose feature defaults are known to the parser.
+pub(crate) const SUPPORTED_EDITIONS: [&str; 2] = ["2023", "2024"];
+
+/// Controls whether a field tracks presence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum FieldPresence {
+    /// Presence is tracked, so an unset field can be told apart from a default value
+    Explicit,
+    /// Presence is not tracked, a field set to its default value is treated as unset
+    Implicit,
+    /// The field must be set (proto2 `required`)
+    LegacyRequired,
+}
+
+/// Controls whether an enum accepts unknown values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum EnumType {
+    /// Unknown values are stored in the field
+    Open,
+    /// Unknown values are treated as unknown fields
+    Closed,
+}
+
+/// Controls how repeated scalar fields are encoded on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RepeatedFieldEncoding {
+    /// All values are written in a single length-delimited record
+    Packed,
+    /// Every value is written as a separate record
+    Expanded,
+}
+
+/// Controls whether string fields are validated as UTF-8 when parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Utf8Validation {
+    /// Invalid UTF-8 is rejected
+    Verify,
+    /// No validation is performed
+    None,
+}
+
+/// Controls how message fields are encoded on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum MessageEncoding {
+    /// Messages are written as length-delimited records
+    LengthPrefixed,
+    /// Messages are written between start and end group tags
+    Delimited,
+}
+
+/// Controls which JSON mappings are allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum JsonFormat {
+    /// Only definitions with a well-defined JSON mapping are allowed
+    Allow,
+    /// JSON mapping conflicts are handled on a best-effort basis
+    LegacyBestEffort,
+}
+
+/// Represents a set of Protobuf Editions features.
+///
+/// Sets parsed from options only contain the features that were declared, while
+/// resolved sets returned by [`Proto::resolve_features`] have every feature set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeatureSet {
+    /// Value of `features.field_presence`
+    pub field_presence: Option<FieldPresence>,
+    /// Value of `features.enum_type`
+    pub enum_type: Option<EnumType>,
+    /// Value of `features.repeated_field_encoding`
+    pub repeated_field_encoding: Option<RepeatedFieldEncoding>,
+    /// Value of `features.utf8_validation`
+    pub utf8_validation: Option<Utf8Validation>,
+    /// Value of `features.message_encoding`
+    pub message_encoding: Option<MessageEncoding>,
+    /// Value of `features.json_format`
+    pub json_format: Option<JsonFormat>,
+}
+
+impl FeatureSet {
+    /// Returns the feature defaults of a file with the given syntax and edition.
+    pub fn defaults(syntax: &str, edition: Option<&str>) -> Self {
+        match (syntax, edition) {
+            (_, Some(_)) => FeatureSet {
+                field_presence: Some(FieldPresence::Explicit),
+                enum_type: Some(EnumType::Open),
+                repeated_field_encoding: Some(RepeatedFieldEncoding::Packed),
+                utf8_validation: Some(Utf8Validation::Verify),
+                message_encoding: Some(MessageEncoding::LengthPrefixed),
+                json_format: Some(JsonFormat::Allow),
+            },
+            ("proto2", None) => FeatureSet {
+                field_presence: Some(FieldPresence::Explicit),
+                enum_type: Some(EnumType::Closed),
+                repeated_field_encoding: Some(RepeatedFieldEncoding::Expanded),
+                utf8_validation: Some(Utf8Validation::None),
+                message_encoding: Some(MessageEncoding::LengthPrefixed),
+                json_format: Some(JsonFormat::LegacyBestEffort),
+            },
+            _ => FeatureSet {
+                field_presence: Some(FieldPresence::Implicit),
+                enum_type: Some(EnumType::Open),
+                repeated_field_encoding: Some(RepeatedFieldEncoding::Packed),
+                utf8_validation: Some(Utf8Validation::Verify),
+                message_encoding: Some(MessageEncoding::LengthPrefixed),
+                json_format: Some(JsonFormat::Allow),
+            },
+        }
+    }
+
+    /// Collects the features declared by `features.*` options.
+    ///
+    /// Language-specific features (e.g. `features.(pb.cpp).legacy_closed_enum`) are skipped.
+    pub fn from_options(options: &[OptionDef]) -> Result<Self, ParserError> {
+        let mut features = FeatureSet::default();
+
+        for option in options {
+            let Some(feature) = option.name.strip_prefix("features.") else {
+                continue;
+            };
+            if feature.starts_with('(') {
+                continue;
+            }
+
+            let value = match &option.value {
+                Constant::Identifier(value) => value.as_str(),
+                _ => "",
+            };
+            let invalid = || {
+                ParserError::ValidationError(format!(
+                    "invalid value for feature '{}': {:?}",
+                    feature, option.value
+                ))
+            };
+
+            match feature {
+                "field_presence" => {
+                    features.field_presence = Some(match value {
+                        "EXPLICIT" => FieldPresence::Explicit,
+                        "IMPLICIT" => FieldPresence::Implicit,
+                        "LEGACY_REQUIRED" => FieldPresence::LegacyRequired,
+                        _ => return Err(invalid()),
+                    })
+                }
+                "enum_type" => {
+                    features.enum_type = Some(match value {
+                        "OPEN" => EnumType::Open,
+                        "CLOSED" => EnumType::Closed,
+                        _ => return Err(invalid()),
+                    })
+                }
+                "repeated_field_encoding" => {
+                    features.repeated_field_encoding = Some(match value {
+                        "PACKED" => RepeatedFieldEncoding::Packed,
+                        "EXPANDED" => RepeatedFieldEncoding::Expanded,
+                        _ => return Err(invalid()),
+                    })
+                }
+                "utf8_validation" => {
+                    features.utf8_validation = Some(match value {
+                        "VERIFY" => Utf8Validation::Verify,
+                        "NONE" => Utf8Validation::None,
+                        _ => return Err(invalid()),
+                    })
+                }
+                "message_encoding" => {
+                    features.message_encoding = Some(match value {
+                        "LENGTH_PREFIXED" => MessageEncoding::LengthPrefixed,
+                        "DELIMITED" => MessageEncoding::Delimited,
+                        _ => return Err(invalid()),
+                    })
+                }
+                "json_format" => {
+                    features.json_format = Some(match value {
+                        "ALLOW" => JsonFormat::Allow,
+                        "LEGACY_BEST_EFFORT" => JsonFormat::LegacyBestEffort,
+                        _ => return Err(invalid()),
+                    })
+                }
+                _ => {
+                    return Err(ParserError::ValidationError(format!(
+                        "unknown feature '{}'",
+                        feature
+                    )))
+                }
+            }
+        }
+
+        Ok(features)
+    }
+
+    /// Returns this set with every feature declared in `child` overriding the inherited value.
+    pub fn merge(self, child: FeatureSet) -> Self {
+        FeatureSet {
+            field_presence: child.field_presence.or(self.field_presence),
+            enum_type: child.enum_type.or(self.enum_type),
+            repeated_field_encoding: child
+                .repeated_field_encoding
+                .or(self.repeated_field_encoding),
+            utf8_validation: child.utf8_validation.or(self.utf8_validation),
+            message_encoding: child.message_encoding.or(self.message_encoding),
+            json_format: child.json_format.or(self.json_format),
+        }
+    }
+}
+
+impl Proto {
+    /// Computes the effective features of every definition in the file.
+    ///
+    /// The result is keyed by fully-qualified name: messages, enums and services use their
+    /// full name (e.g. `acme.v1.User`), while fields, oneofs, enum values and methods are
+    /// nested under their parent (e.g. `acme.v1.User.name`, `acme.v1.Status.ACTIVE`).
+    /// Extension fields are keyed under the scope of their `extend` block.
+    ///
+    /// For proto2 and proto3 files the legacy behaviour is translated into features as well,
+    /// so `required` fields resolve to `LEGACY_REQUIRED` and proto3 `optional` fields to `EXPLICIT`.
+    pub fn resolve_features(&self) -> Result<BTreeMap<String, FeatureSet>, ParserError> {
+        let mut resolver = FeatureResolver {
+            legacy: self.edition.is_none(),
+            proto3: self.edition.is_none() && self.syntax == "proto3",
+            resolved: BTreeMap::new(),
+        };
+
+        let file = FeatureSet::defaults(&self.syntax, self.edition.as_deref())
+            .merge(FeatureSet::from_options(&self.options)?);
+        let scope = self.package.clone().unwrap_or_default();
+
+        for message in &self.messages {
+            resolver.message(message, &scope, file)?;
+        }
+        for enum_def in &self.enums {
+            resolver.enum_def(enum_def, &scope, file)?;
+        }
+        for extend in &self.extends {
+            for field in &extend.fields {
+                resolver.field(field, &scope, file, false)?;
+            }
+        }
+        for service in &self.services {
+            resolver.service(service, &scope, file)?;
+        }
+
+        Ok(resolver.resolved)
+    }
+}
+
+/// Walks the definitions of a file and records their resolved features.
+struct FeatureResolver {
+    /// Indicates if the file uses proto2/proto3 syntax instead of an edition
+    legacy: bool,
+    /// Indicates if the file uses proto3 syntax
+    proto3: bool,
+    /// Resolved features keyed by fully-qualified name
+    resolved: BTreeMap<String, FeatureSet>,
+}
+
+impl FeatureResolver {
+    fn message(
+        &mut self,
+        message: &Message,
+        scope: &str,
+        parent: FeatureSet,
+    ) -> Result<(), ParserError> {
+        let name = Proto::qualify(scope, &message.name);
+        let features = parent.merge(FeatureSet::from_options(&message.options)?);
+        self.resolved.insert(name.clone(), features);
+
+        for field in &message.fields {
+            self.field(field, &name, features, false)?;
+        }
+        for oneof in &message.oneofs {
+            let oneof_features = features.merge(FeatureSet::from_options(&oneof.options)?);
+            self.resolved
+                .insert(Proto::qualify(&name, &oneof.name), oneof_features);
+            for field in &oneof.fields {
+                self.field(field, &name, oneof_features, true)?;
+            }
+        }
+        for nested in &message.nested_messages {
+            self.message(nested, &name, features)?;
+        }
+        for nested in &message.nested_enums {
+            self.enum_def(nested, &name, features)?;
+        }
+        for extend in &message.extends {
+            for field in &extend.fields {
+                self.field(field, &name, features, false)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn field(
+        &mut self,
+        field: &Field,
+        scope: &str,
+        parent: FeatureSet,
+        in_oneof: bool,
+    ) -> Result<(), ParserError> {
+        let mut features = parent.merge(FeatureSet::from_options(&field.options)?);
+
+        if self.legacy {
+            if field.label == Label::Required {
+                features.field_presence = Some(FieldPresence::LegacyRequired);
+            } else if in_oneof || (self.proto3 && field.label == Label::Optional) {
+                features.field_presence = Some(FieldPresence::Explicit);
+            }
+            match field.packed {
+                Some(true) => {
+                    features.repeated_field_encoding = Some(RepeatedFieldEncoding::Packed)
+                }
+                Some(false) => {
+                    features.repeated_field_encoding = Some(RepeatedFieldEncoding::Expanded)
+                }
+                None => {}
+            }
+            if matches!(field.kind, FieldKind::Group) {
+                features.message_encoding = Some(MessageEncoding::Delimited);
+            }
+        } else if in_oneof {
+            features.field_presence = Some(FieldPresence::Explicit);
+        }
+
+        self.resolved
+            .insert(Proto::qualify(scope, &field.name), features);
+
+        Ok(())
+    }
+
+    fn enum_def(
+        &mut self,
+        enum_def: &EnumDef,
+        scope: &str,
+        parent: FeatureSet,
+    ) -> Result<(), ParserError> {
+        let name = Proto::qualify(scope, &enum_def.name);
+        let features = parent.merge(FeatureSet::from_options(&enum_def.options)?);
+        self.resolved.insert(name.clone(), features);
+
+        for value in &enum_def.values {
+            let value_features = features.merge(FeatureSet::from_options(&value.options)?);
+            self.resolved
+                .insert(Proto::qualify(&name, &value.name), value_features);
+        }
+
+        Ok(())
+    }
+
+    fn service(
+        &mut self,
+        service: &Service,
+        scope: &str,
+        parent: FeatureSet,
+    ) -> Result<(), ParserError> {
+        let name = Proto::qualify(scope, &service.name);
+        let features = parent.merge(FeatureSet::from_options(&service.options)?);
+        self.resolved.insert(name.clone(), features);
+
+        for method in &service.methods {
+            let method_features = features.merge(FeatureSet::from_options(&method.options)?);
+            self.resolved
+                .insert(Proto::qualify(&name, &method.name), method_features);
+        }
+
+        Ok(())
+    }
+}
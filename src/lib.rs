@@ -1,8 +1,44 @@
 use pest::Parser;
 use pest_derive::Parser;
-use serde::Serialize;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
 use thiserror::Error;
 
+mod builder;
+mod comments;
+mod features;
+mod printer;
+mod span;
+pub mod visit;
+
+pub use builder::{
+    EnumBuilder, EnumValueBuilder, ExtendBuilder, FieldBuilder, MessageBuilder, MethodBuilder,
+    OneofBuilder, ProtoBuilder, ServiceBuilder,
+};
+pub use comments::Comments;
+pub use features::{
+    EnumType, FeatureSet, FieldPresence, JsonFormat, MessageEncoding, RepeatedFieldEncoding,
+    Utf8Validation,
+};
+pub use span::Span;
+pub use visit::{Visit, VisitMut};
+
+/// Largest allowed field number, used as the value of `max` in message ranges.
+const MAX_FIELD_NUMBER: i32 = 536_870_911;
+
+/// Numbers that can be assigned to fields.
+const FIELD_NUMBERS: RangeInclusive<i32> = 1..=MAX_FIELD_NUMBER;
+
+/// Numbers that can be assigned to enum values.
+const ENUM_NUMBERS: RangeInclusive<i32> = i32::MIN..=i32::MAX;
+
+/// Scalar types that are allowed as keys of a map field.
+const MAP_KEY_TYPES: [&str; 12] = [
+    "int32", "int64", "uint32", "uint64", "sint32", "sint64", "fixed32", "fixed64", "sfixed32",
+    "sfixed64", "bool", "string",
+];
+
 /// Parser implementation using pest grammar rules.
 /// This struct is used to parse Protocol Buffer files according to the grammar defined in proto.pest.
 #[derive(Parser)]
@@ -18,105 +54,586 @@ pub enum ParserError {
 
     /// Indicates an error during the parsing process
     #[error("Parse error: {0}")]
-    ParseError(#[from] pest::error::Error<Rule>),
+    ParseError(#[from] Box<pest::error::Error<Rule>>),
 
     /// Indicates an error during file operations
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
 
-    /// Indicates an error during JSON serialization
+    /// Indicates an error during JSON serialization or deserialization
     #[error("JSON serialization error: {0}")]
     SerializationError(#[from] serde_json::Error),
+
+    /// Indicates a definition that is syntactically valid but violates protobuf semantics
+    #[error("Validation error: {0}")]
+    ValidationError(String),
+}
+
+impl From<pest::error::Error<Rule>> for ParserError {
+    fn from(error: pest::error::Error<Rule>) -> Self {
+        ParserError::ParseError(Box::new(error.renamed_rules(rule_name)))
+    }
+}
+
+/// Names a grammar rule in parse errors.
+///
+/// Keyword rules are only used in lookaheads, so they are named after the statement they start.
+fn rule_name(rule: &Rule) -> String {
+    let rule = match rule {
+        Rule::kw_syntax => Rule::syntax,
+        Rule::kw_edition => Rule::edition,
+        Rule::kw_package => Rule::package,
+        Rule::kw_import => Rule::import,
+        Rule::kw_message => Rule::message_def,
+        Rule::kw_map => Rule::map_field,
+        Rule::kw_group => Rule::group,
+        Rule::kw_oneof => Rule::oneof_def,
+        Rule::kw_option => Rule::option_stmt,
+        Rule::kw_reserved => Rule::reserved,
+        Rule::kw_extensions => Rule::extensions,
+        Rule::kw_extend => Rule::extend_def,
+        Rule::kw_enum => Rule::enum_def,
+        Rule::kw_service => Rule::service_def,
+        Rule::kw_rpc => Rule::rpc_def,
+        Rule::kw_to => return "to".to_string(),
+        Rule::kw_returns => return "returns".to_string(),
+        rule => *rule,
+    };
+    format!("{:?}", rule)
 }
 
 /// Main structure representing a complete Protocol Buffer file.
 /// Contains all the elements that can be defined in a proto file.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Proto {
-    /// The syntax version specified in the proto file (e.g., "proto3")
-    syntax: String,
+    /// The syntax version specified in the proto file (e.g., "proto3"), or "editions"
+    /// if the file declares an edition instead
+    pub syntax: String,
+    /// The edition specified in the proto file (e.g., "2023"), if any
+    pub edition: Option<String>,
     /// Optional package name that scopes the proto definitions
-    package: Option<String>,
+    pub package: Option<String>,
     /// List of other proto files that are imported
-    imports: Vec<String>,
+    pub imports: Vec<Import>,
     /// List of message type definitions
-    messages: Vec<Message>,
+    pub messages: Vec<Message>,
     /// List of enum type definitions
-    enums: Vec<EnumDef>,
+    pub enums: Vec<EnumDef>,
     /// List of service definitions
-    services: Vec<Service>,
+    pub services: Vec<Service>,
+    /// List of extend blocks declared at the top level
+    pub extends: Vec<Extend>,
+    /// List of file-level options
+    pub options: Vec<OptionDef>,
+    /// Messages, enums, services and extend blocks in the order they are declared
+    pub declarations: Vec<Declaration>,
+    /// Location in the source file
+    #[serde(default, skip_serializing_if = "Span::is_unknown")]
+    pub span: Span,
+}
+
+/// JSON output of a parsed proto file: the file itself together with
+/// the resolved features of all of its definitions.
+#[derive(Serialize)]
+struct ProtoOutput<'a> {
+    /// The parsed proto file
+    #[serde(flatten)]
+    proto: &'a Proto,
+    /// Effective features keyed by fully-qualified name, see [`Proto::resolve_features`]
+    resolved_features: BTreeMap<String, FeatureSet>,
+}
+
+/// Represents an import statement (e.g. `import public "other.proto";`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Import {
+    /// Path of the imported proto file
+    pub path: String,
+    /// Modifier changing how the import is treated, if any
+    pub modifier: Option<ImportModifier>,
+    /// Comments attached to the definition
+    #[serde(default, skip_serializing_if = "Comments::is_empty")]
+    pub comments: Comments,
+    /// Location in the source file
+    #[serde(default, skip_serializing_if = "Span::is_unknown")]
+    pub span: Span,
+}
+
+/// Represents the modifier of an import statement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportModifier {
+    /// The imported definitions are re-exported to files importing this one
+    Public,
+    /// The import may be missing at runtime
+    Weak,
 }
 
 /// Represents a message definition in the proto file.
 /// Messages are user-defined composite types.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Message {
     /// Name of the message type
-    name: String,
+    pub name: String,
     /// List of fields contained in the message
-    fields: Vec<Field>,
+    pub fields: Vec<Field>,
+    /// List of oneof groups; each holds its mutually exclusive member fields
+    pub oneofs: Vec<Oneof>,
     /// List of message types defined within this message
-    nested_messages: Vec<Message>,
+    pub nested_messages: Vec<Message>,
     /// List of enum types defined within this message
-    nested_enums: Vec<EnumDef>,
+    pub nested_enums: Vec<EnumDef>,
+    /// List of extend blocks declared within this message
+    pub extends: Vec<Extend>,
+    /// List of field number ranges available to extensions
+    pub extension_ranges: Vec<ExtensionRange>,
+    /// List of field number ranges that are reserved and cannot be used
+    pub reserved_ranges: Vec<Range>,
+    /// List of field names that are reserved and cannot be used
+    pub reserved_names: Vec<String>,
+    /// List of message-level options
+    pub options: Vec<OptionDef>,
+    /// Fields, oneofs, nested messages, nested enums and extend blocks in the order
+    /// they are declared
+    pub declarations: Vec<Declaration>,
+    /// Comments attached to the definition
+    #[serde(default, skip_serializing_if = "Comments::is_empty")]
+    pub comments: Comments,
+    /// Location in the source file
+    #[serde(default, skip_serializing_if = "Span::is_unknown")]
+    pub span: Span,
+}
+
+/// Refers to a definition by its position in the list of definitions of the same kind,
+/// e.g. `{ kind: Enum, index: 1 }` is the second enum of a file or message.
+///
+/// A group declares a field and a message at once, so it is listed as both, field first.
+/// Messages of groups inside a oneof or an extend block are listed right after the oneof
+/// or block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Declaration {
+    /// Kind of the definition
+    pub kind: DeclarationKind,
+    /// Index of the definition in the list of its kind
+    pub index: usize,
+}
+
+/// Represents the kind of a declared definition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeclarationKind {
+    /// A message, in `messages` or `nested_messages`
+    Message,
+    /// An enum, in `enums` or `nested_enums`
+    Enum,
+    /// A service, in `services` (top level only)
+    Service,
+    /// An extend block, in `extends`
+    Extend,
+    /// A field, in `fields` (messages only)
+    Field,
+    /// A oneof, in `oneofs` (messages only)
+    Oneof,
 }
 
 /// Represents a field within a message.
 /// Fields are the basic components of a message.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Field {
     /// Name of the field
-    name: String,
-    /// Type of the field (can be primitive type or another message type)
-    type_name: String,
+    pub name: String,
+    /// Type of the field (can be primitive type or another message type).
+    /// Fully-qualified type references keep their leading dot (e.g. `.acme.v1.User`),
+    /// relative ones are stored as written (e.g. `google.protobuf.Timestamp`)
+    pub type_name: String,
     /// Unique numerical tag that identifies the field in the message
-    tag: i32,
-    /// Indicates if the field is a repeated field (array/list)
-    repeated: bool,
+    pub tag: i32,
+    /// Cardinality of the field as declared by its label
+    pub label: Label,
+    /// Structural kind of the field (regular or map)
+    pub kind: FieldKind,
+    /// Value of the `deprecated` option, if set
+    pub deprecated: Option<bool>,
+    /// Value of the `packed` option, if set
+    pub packed: Option<bool>,
+    /// Value of the `json_name` option, if set
+    pub json_name: Option<String>,
+    /// Value of the proto2 `default` option, if set
+    pub default: Option<Constant>,
+    /// Value of the `lazy` option, if set
+    pub lazy: Option<bool>,
+    /// Value of the `debug_redact` option, if set
+    pub debug_redact: Option<bool>,
+    /// List of other options given in brackets after the field tag
+    pub options: Vec<OptionDef>,
+    /// Comments attached to the definition
+    #[serde(default, skip_serializing_if = "Comments::is_empty")]
+    pub comments: Comments,
+    /// Location in the source file
+    #[serde(default, skip_serializing_if = "Span::is_unknown")]
+    pub span: Span,
+}
+
+/// Represents the label (cardinality) of a field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Label {
+    /// No label was given (proto3 singular field with implicit presence, or a oneof member)
+    Implicit,
+    /// The field is explicitly `optional` and tracks presence
+    Optional,
+    /// The field is `required` (proto2 only)
+    Required,
+    /// The field is `repeated` (array/list); map fields are always repeated
+    Repeated,
+}
+
+impl Label {
+    /// Converts the text of a `field_rule` into a label.
+    fn from_rule(rule: &str) -> Self {
+        match rule {
+            "optional" => Label::Optional,
+            "required" => Label::Required,
+            "repeated" => Label::Repeated,
+            _ => Label::Implicit,
+        }
+    }
+}
+
+/// Distinguishes regular fields from fields with a special structure.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldKind {
+    /// A regular field of a primitive, message or enum type
+    Normal,
+    /// A map field (`map<key_type, value_type>`)
+    Map {
+        /// Type of the map keys (an integral type, `bool` or `string`)
+        key_type: String,
+        /// Type of the map values (any type except another map), written like `type_name`
+        value_type: String,
+    },
+    /// A proto2 group field, whose type is the message declared by the group
+    Group,
+}
+
+/// Represents a oneof definition within a message.
+/// At most one of the member fields can be set at the same time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Oneof {
+    /// Name of the oneof
+    pub name: String,
+    /// List of fields that are members of this oneof
+    pub fields: Vec<Field>,
+    /// List of options applied to this oneof
+    pub options: Vec<OptionDef>,
+    /// Comments attached to the definition
+    #[serde(default, skip_serializing_if = "Comments::is_empty")]
+    pub comments: Comments,
+    /// Location in the source file
+    #[serde(default, skip_serializing_if = "Span::is_unknown")]
+    pub span: Span,
+}
+
+/// Represents an option, either as a statement (e.g. `option deprecated = true;`)
+/// or as a compact option in brackets (e.g. `[packed = false]`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OptionDef {
+    /// Name of the option; custom options keep their parentheses (e.g. `(my.ext).sub`)
+    pub name: String,
+    /// Value assigned to the option
+    pub value: Constant,
+    /// Comments attached to the definition
+    #[serde(default, skip_serializing_if = "Comments::is_empty")]
+    pub comments: Comments,
+    /// Location in the source file
+    #[serde(default, skip_serializing_if = "Span::is_unknown")]
+    pub span: Span,
+}
+
+/// Represents a constant value used in options.
+///
+/// Floats compare equal if they have the same bits, or if both are `nan`, so that a
+/// definition containing `nan` is equal to itself.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Constant {
+    /// An identifier, such as an enum value name (e.g. `SPEED`)
+    Identifier(String),
+    /// An integer literal that fits into a signed 64-bit integer
+    Integer(i64),
+    /// A positive integer literal too large for a signed 64-bit integer
+    UnsignedInteger(u64),
+    /// A floating-point literal, including `inf` and `nan`
+    #[serde(
+        serialize_with = "serialize_float",
+        deserialize_with = "deserialize_float"
+    )]
+    Float(f64),
+    /// A string literal
+    String(String),
+    /// A string literal whose escapes produce bytes that are not valid UTF-8 (e.g. `"\377"`)
+    Bytes(Vec<u8>),
+    /// A boolean literal (`true` or `false`)
+    Bool(bool),
+    /// An aggregate value in text format (e.g. `{ a: 1 b: "x" }`)
+    Aggregate(Vec<AggregateField>),
+    /// A list of values, only allowed inside aggregates (e.g. `[1, 2]`)
+    List(Vec<Constant>),
+}
+
+impl PartialEq for Constant {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Constant::Identifier(a), Constant::Identifier(b)) => a == b,
+            (Constant::Integer(a), Constant::Integer(b)) => a == b,
+            (Constant::UnsignedInteger(a), Constant::UnsignedInteger(b)) => a == b,
+            (Constant::Float(a), Constant::Float(b)) => {
+                a.to_bits() == b.to_bits() || (a.is_nan() && b.is_nan())
+            }
+            (Constant::String(a), Constant::String(b)) => a == b,
+            (Constant::Bytes(a), Constant::Bytes(b)) => a == b,
+            (Constant::Bool(a), Constant::Bool(b)) => a == b,
+            (Constant::Aggregate(a), Constant::Aggregate(b)) => a == b,
+            (Constant::List(a), Constant::List(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+/// Serializes a float constant, writing values that JSON cannot represent
+/// (`inf`, `-inf` and `nan`) as strings.
+fn serialize_float<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+    if value.is_finite() {
+        serializer.serialize_f64(*value)
+    } else if value.is_nan() {
+        serializer.serialize_str("nan")
+    } else if value.is_sign_positive() {
+        serializer.serialize_str("inf")
+    } else {
+        serializer.serialize_str("-inf")
+    }
+}
+
+/// Deserializes a float constant written by [`serialize_float`].
+fn deserialize_float<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Float {
+        Number(f64),
+        Text(String),
+    }
+
+    match Float::deserialize(deserializer)? {
+        Float::Number(value) => Ok(value),
+        Float::Text(text) => match text.as_str() {
+            "inf" => Ok(f64::INFINITY),
+            "-inf" => Ok(f64::NEG_INFINITY),
+            "nan" => Ok(f64::NAN),
+            _ => Err(serde::de::Error::custom(format!(
+                "invalid float value \"{}\"",
+                text
+            ))),
+        },
+    }
+}
+
+/// Represents a single `name: value` entry of an aggregate constant.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AggregateField {
+    /// Name of the field; extension names keep their brackets (e.g. `[my.ext]`)
+    pub name: String,
+    /// Value assigned to the field
+    pub value: Constant,
 }
 
 /// Represents an enumeration definition.
 /// Enums are a type that can have one of a predefined set of values.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EnumDef {
     /// Name of the enum type
-    name: String,
+    pub name: String,
     /// List of possible values for this enum
-    values: Vec<EnumValue>,
+    pub values: Vec<EnumValue>,
+    /// List of value number ranges that are reserved and cannot be used
+    pub reserved_ranges: Vec<Range>,
+    /// List of value names that are reserved and cannot be used
+    pub reserved_names: Vec<String>,
+    /// List of enum-level options
+    pub options: Vec<OptionDef>,
+    /// List of values that reuse the number of an earlier value (requires `allow_alias`)
+    pub aliases: Vec<EnumAlias>,
+    /// Comments attached to the definition
+    #[serde(default, skip_serializing_if = "Comments::is_empty")]
+    pub comments: Comments,
+    /// Location in the source file
+    #[serde(default, skip_serializing_if = "Span::is_unknown")]
+    pub span: Span,
+}
+
+/// Represents an inclusive range of field or enum value numbers (e.g. `9 to 11`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Range {
+    /// First number of the range
+    pub start: i32,
+    /// Last number of the range, equal to `start` for a single number
+    pub end: i32,
+    /// Location in the source file
+    #[serde(default, skip_serializing_if = "Span::is_unknown")]
+    pub span: Span,
+}
+
+/// Represents an inclusive range of field numbers reserved for extensions (e.g. `100 to 199`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExtensionRange {
+    /// First field number of the range
+    pub start: i32,
+    /// Last field number of the range, equal to `start` for a single number
+    pub end: i32,
+    /// List of options given in brackets after the ranges
+    pub options: Vec<OptionDef>,
+    /// Location in the source file
+    #[serde(default, skip_serializing_if = "Span::is_unknown")]
+    pub span: Span,
+}
+
+/// Represents an `extend` block that adds extension fields to another message.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Extend {
+    /// Name of the message being extended, written like `Field::type_name`
+    pub extendee: String,
+    /// List of extension fields declared in the block
+    pub fields: Vec<Field>,
+    /// Fully-qualified scope the block is declared in (package and enclosing messages)
+    pub scope: String,
+    /// Comments attached to the definition
+    #[serde(default, skip_serializing_if = "Comments::is_empty")]
+    pub comments: Comments,
+    /// Location in the source file
+    #[serde(default, skip_serializing_if = "Span::is_unknown")]
+    pub span: Span,
 }
 
 /// Represents a single value in an enum definition.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EnumValue {
     /// Name of the enum value (should be UPPERCASE_WITH_UNDERSCORES by convention)
-    name: String,
+    pub name: String,
     /// Integer value associated with this enum value
-    number: i32,
+    pub number: i32,
+    /// Value of the `deprecated` option, if set
+    pub deprecated: Option<bool>,
+    /// Value of the `debug_redact` option, if set
+    pub debug_redact: Option<bool>,
+    /// List of other options given in brackets after the value number
+    pub options: Vec<OptionDef>,
+    /// Comments attached to the definition
+    #[serde(default, skip_serializing_if = "Comments::is_empty")]
+    pub comments: Comments,
+    /// Location in the source file
+    #[serde(default, skip_serializing_if = "Span::is_unknown")]
+    pub span: Span,
+}
+
+/// Represents an enum value that shares its number with an earlier value of the same enum.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnumAlias {
+    /// Name of the aliasing value
+    pub name: String,
+    /// Name of the first value declared with the same number
+    pub target: String,
+    /// Number shared by both values
+    pub number: i32,
 }
 
 /// Represents a service definition.
 /// Services define methods that can be called remotely.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Service {
     /// Name of the service
-    name: String,
+    pub name: String,
     /// List of methods provided by this service
-    methods: Vec<Method>,
+    pub methods: Vec<Method>,
+    /// List of service-level options
+    pub options: Vec<OptionDef>,
+    /// Comments attached to the definition
+    #[serde(default, skip_serializing_if = "Comments::is_empty")]
+    pub comments: Comments,
+    /// Location in the source file
+    #[serde(default, skip_serializing_if = "Span::is_unknown")]
+    pub span: Span,
 }
 
 /// Represents an RPC method in a service definition.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Method {
     /// Name of the method
-    name: String,
-    /// Type of the input message
-    input_type: String,
-    /// Type of the output message
-    output_type: String,
+    pub name: String,
+    /// Type of the input message, written like `Field::type_name`
+    pub input_type: String,
+    /// Type of the output message, written like `Field::type_name`
+    pub output_type: String,
+    /// Indicates if the client sends a stream of input messages
+    pub client_streaming: bool,
+    /// Indicates if the server sends a stream of output messages
+    pub server_streaming: bool,
+    /// List of options declared in the method body
+    pub options: Vec<OptionDef>,
+    /// Comments attached to the definition
+    #[serde(default, skip_serializing_if = "Comments::is_empty")]
+    pub comments: Comments,
+    /// Location in the source file
+    #[serde(default, skip_serializing_if = "Span::is_unknown")]
+    pub span: Span,
+}
+
+impl Message {
+    /// Creates an empty message with the given name and location.
+    fn new(name: String, span: Span) -> Self {
+        Message {
+            name,
+            fields: Vec::new(),
+            oneofs: Vec::new(),
+            nested_messages: Vec::new(),
+            nested_enums: Vec::new(),
+            extends: Vec::new(),
+            extension_ranges: Vec::new(),
+            reserved_ranges: Vec::new(),
+            reserved_names: Vec::new(),
+            options: Vec::new(),
+            declarations: Vec::new(),
+            comments: Comments::default(),
+            span,
+        }
+    }
+
+    /// Returns the lengths of the lists of definitions recorded in `declarations`.
+    fn definition_counts(&self) -> [(DeclarationKind, usize); 5] {
+        [
+            (DeclarationKind::Field, self.fields.len()),
+            (DeclarationKind::Oneof, self.oneofs.len()),
+            (DeclarationKind::Extend, self.extends.len()),
+            (DeclarationKind::Message, self.nested_messages.len()),
+            (DeclarationKind::Enum, self.nested_enums.len()),
+        ]
+    }
+}
+
+/// Records the declaration of the definitions added to lists since their lengths were taken.
+///
+/// `before` and `after` hold the length of every list before and after parsing a statement,
+/// in the order in which the definitions of a single statement are declared.
+fn declare<const N: usize>(
+    declarations: &mut Vec<Declaration>,
+    before: [(DeclarationKind, usize); N],
+    after: [(DeclarationKind, usize); N],
+) {
+    for ((kind, start), (_, end)) in before.into_iter().zip(after) {
+        declarations.extend((start..end).map(|index| Declaration { kind, index }));
+    }
 }
 
 impl Proto {
-    /// Parses a proto file from the filesystem and returns its JSON representation.
+    /// Parses a proto file from the filesystem.
     ///
     /// # Arguments
     ///
@@ -124,7 +641,7 @@ impl Proto {
     ///
     /// # Returns
     ///
-    /// A Result containing either the JSON string representation of the parsed proto file
+    /// A Result containing either the parsed proto file
     /// or a ParserError if any error occurs during parsing or processing.
     ///
     /// # Examples
@@ -132,15 +649,15 @@ impl Proto {
     /// ```no_run
     /// use proto_file_parser::Proto;
     ///
-    /// let json = Proto::parse_file("example.proto").unwrap();
-    /// println!("{}", json);
+    /// let proto = Proto::parse_file("example.proto").unwrap();
+    /// println!("{}", proto.to_json().unwrap());
     /// ```
-    pub fn parse_file(path: &str) -> Result<String, ParserError> {
+    pub fn parse_file(path: &str) -> Result<Proto, ParserError> {
         let content = std::fs::read_to_string(path)?;
         Self::parse(&content)
     }
 
-    /// Parses a proto definition from a string and returns its JSON representation.
+    /// Parses a proto definition from a string.
     ///
     /// # Arguments
     ///
@@ -148,7 +665,7 @@ impl Proto {
     ///
     /// # Returns
     ///
-    /// A Result containing either the JSON string representation of the parsed proto definition
+    /// A Result containing either the parsed proto definition
     /// or a ParserError if any error occurs during parsing or processing.
     ///
     /// # Examples
@@ -163,86 +680,213 @@ impl Proto {
     ///     }
     /// "#;
     ///
-    /// let json = Proto::parse(input).unwrap();
-    /// println!("{}", json);
+    /// let proto = Proto::parse(input).unwrap();
+    /// assert_eq!(proto.messages[0].name, "Test");
+    /// assert_eq!(proto.messages[0].fields[0].type_name, "string");
     /// ```
-    pub fn parse(input: &str) -> Result<String, ParserError> {
+    pub fn parse(input: &str) -> Result<Proto, ParserError> {
+        Self::parse_with_file_id(input, 0)
+    }
+
+    /// Parses a proto definition from a string, recording `file_id` in all of its spans.
+    ///
+    /// The file id is not interpreted by the parser; it lets callers that parse several files
+    /// tell which file a [`Span`] belongs to.
+    pub fn parse_with_file_id(input: &str, file_id: usize) -> Result<Proto, ParserError> {
         let pairs = ProtoParser::parse(Rule::proto_file, input)?;
 
         let mut proto = Proto {
             syntax: "proto3".to_string(),
+            edition: None,
             package: None,
             imports: Vec::new(),
             messages: Vec::new(),
             enums: Vec::new(),
             services: Vec::new(),
+            extends: Vec::new(),
+            options: Vec::new(),
+            declarations: Vec::new(),
+            span: Span::default(),
         };
+        let mut comments = BTreeMap::new();
 
         for pair in pairs {
-            match pair.as_rule() {
-                Rule::proto_file => {
-                    for inner_pair in pair.into_inner() {
-                        match inner_pair.as_rule() {
-                            Rule::syntax => {
-                                proto.syntax = inner_pair
-                                    .into_inner()
-                                    .next()
-                                    .unwrap()
-                                    .as_str()
-                                    .trim_matches('"')
-                                    .to_string();
-                            }
-                            Rule::package => {
-                                proto.package = Some(
-                                    inner_pair
-                                        .into_inner()
-                                        .next()
-                                        .unwrap()
-                                        .as_str()
-                                        .to_string(),
-                                );
-                            }
-                            Rule::import => {
-                                proto.imports.push(
-                                    inner_pair
-                                        .into_inner()
-                                        .next()
-                                        .unwrap()
-                                        .as_str()
-                                        .trim_matches('"')
-                                        .to_string(),
-                                );
-                            }
-                            Rule::message_def => {
-                                proto.messages.push(Self::parse_message(inner_pair)?);
-                            }
-                            Rule::enum_def => {
-                                proto.enums.push(Self::parse_enum(inner_pair)?);
-                            }
-                            Rule::service_def => {
-                                proto.services.push(Self::parse_service(inner_pair)?);
+            if pair.as_rule() == Rule::proto_file {
+                proto.span = Span::from_pair(&pair);
+                comments = comments::collect(input, &pair);
+                for inner_pair in pair.into_inner() {
+                    let before = proto.definition_counts();
+                    match inner_pair.as_rule() {
+                        Rule::syntax => {
+                            proto.syntax =
+                                Self::parse_string(inner_pair.into_inner().next().unwrap())?;
+                        }
+                        Rule::edition => {
+                            let edition_pair = inner_pair.into_inner().next().unwrap();
+                            let edition = Self::parse_string(edition_pair)?;
+                            if !features::SUPPORTED_EDITIONS.contains(&edition.as_str()) {
+                                return Err(ParserError::ValidationError(format!(
+                                    "unsupported edition \"{}\"",
+                                    edition
+                                )));
                             }
-                            Rule::EOI => {}
-                            _ => {}
+                            proto.syntax = "editions".to_string();
+                            proto.edition = Some(edition);
+                        }
+                        Rule::package => {
+                            proto.package =
+                                Some(inner_pair.into_inner().next().unwrap().as_str().to_string());
+                        }
+                        Rule::import => {
+                            proto.imports.push(Self::parse_import(inner_pair)?);
+                        }
+                        Rule::message_def => {
+                            let scope = proto.package.clone().unwrap_or_default();
+                            proto
+                                .messages
+                                .push(Self::parse_message(inner_pair, &scope)?);
+                        }
+                        Rule::enum_def => {
+                            proto.enums.push(Self::parse_enum(inner_pair)?);
                         }
+                        Rule::service_def => {
+                            proto.services.push(Self::parse_service(inner_pair)?);
+                        }
+                        Rule::extend_def => {
+                            let scope = proto.package.clone().unwrap_or_default();
+                            let extend =
+                                Self::parse_extend(inner_pair, &scope, &mut proto.messages)?;
+                            proto.extends.push(extend);
+                        }
+                        Rule::option_stmt => {
+                            proto.options.push(Self::parse_option(inner_pair)?);
+                        }
+                        Rule::EOI => {}
+                        _ => {}
                     }
+                    let after = proto.definition_counts();
+                    declare(&mut proto.declarations, before, after);
                 }
-                _ => {}
             }
         }
 
-        let json = serde_json::to_string_pretty(&proto)?;
+        // Unknown features and feature values are reported as parse errors
+        proto.resolve_features()?;
+
+        proto.for_each_location(&mut |span, node_comments| {
+            span.file_id = file_id;
+            if let (Some(node_comments), Some(found)) = (node_comments, comments.get(&span.start)) {
+                *node_comments = found.clone();
+            }
+        });
+
+        Ok(proto)
+    }
+
+    /// Renders the proto definition as pretty-printed JSON, without source spans.
+    ///
+    /// Besides the definitions themselves, the JSON contains the resolved features
+    /// of every definition under `resolved_features` (see [`Proto::resolve_features`]).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use proto_file_parser::Proto;
+    ///
+    /// let proto = Proto::parse(r#"syntax = "proto3"; package acme;"#).unwrap();
+    /// let json = proto.to_json().unwrap();
+    /// assert!(json.contains("\"package\": \"acme\""));
+    /// ```
+    pub fn to_json(&self) -> Result<String, ParserError> {
+        self.without_spans().to_json_with_spans()
+    }
+
+    /// Renders the proto definition as pretty-printed JSON, including the `span`
+    /// of every definition that was parsed from source.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use proto_file_parser::Proto;
+    ///
+    /// let proto = Proto::parse("syntax = \"proto3\";\nmessage Test {}").unwrap();
+    /// let json = proto.to_json_with_spans().unwrap();
+    /// let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    /// assert_eq!(value["messages"][0]["span"]["start_line"], 2);
+    /// ```
+    pub fn to_json_with_spans(&self) -> Result<String, ParserError> {
+        let output = ProtoOutput {
+            resolved_features: self.resolve_features()?,
+            proto: self,
+        };
+
+        let json = serde_json::to_string_pretty(&output)?;
         Ok(json)
     }
 
+    /// Loads a proto definition from JSON produced by [`Proto::to_json`].
+    ///
+    /// The `resolved_features` entry is ignored, since features are computed from the
+    /// definitions themselves.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use proto_file_parser::Proto;
+    ///
+    /// let proto = Proto::parse(r#"syntax = "proto3"; message Test { string name = 1; }"#).unwrap();
+    /// let loaded = Proto::from_json(&proto.to_json().unwrap()).unwrap();
+    /// assert!(loaded.eq_ignoring_spans(&proto));
+    /// ```
+    pub fn from_json(json: &str) -> Result<Proto, ParserError> {
+        let proto = serde_json::from_str(json)?;
+        Ok(proto)
+    }
+
+    /// Returns the lengths of the lists of definitions recorded in `declarations`.
+    fn definition_counts(&self) -> [(DeclarationKind, usize); 4] {
+        [
+            (DeclarationKind::Extend, self.extends.len()),
+            (DeclarationKind::Message, self.messages.len()),
+            (DeclarationKind::Enum, self.enums.len()),
+            (DeclarationKind::Service, self.services.len()),
+        ]
+    }
+
+    /// Parses an import statement from a pest Pair.
+    fn parse_import(pair: pest::iterators::Pair<Rule>) -> Result<Import, ParserError> {
+        let span = Span::from_pair(&pair);
+        let mut modifier = None;
+        let mut path = String::new();
+
+        for inner_pair in pair.into_inner() {
+            match inner_pair.as_rule() {
+                Rule::import_modifier => {
+                    modifier = match inner_pair.as_str() {
+                        "public" => Some(ImportModifier::Public),
+                        _ => Some(ImportModifier::Weak),
+                    };
+                }
+                _ => path = Self::parse_string(inner_pair)?,
+            }
+        }
+
+        Ok(Import {
+            path,
+            modifier,
+            comments: Comments::default(),
+            span,
+        })
+    }
+
     /// Parses a message definition from a pest Pair.
-    fn parse_message(pair: pest::iterators::Pair<Rule>) -> Result<Message, ParserError> {
-        let mut message = Message {
-            name: String::new(),
-            fields: Vec::new(),
-            nested_messages: Vec::new(),
-            nested_enums: Vec::new(),
-        };
+    ///
+    /// `scope` is the fully-qualified name of the package or message that contains this message.
+    fn parse_message(
+        pair: pest::iterators::Pair<Rule>,
+        scope: &str,
+    ) -> Result<Message, ParserError> {
+        let mut message = Message::new(String::new(), Span::from_pair(&pair));
 
         let mut pairs = pair.into_inner();
 
@@ -252,22 +896,134 @@ impl Proto {
             }
         }
 
+        Self::parse_message_body(&mut message, pairs, scope)?;
+
+        Ok(message)
+    }
+
+    /// Parses the elements of a message body from pest Pairs into the given message.
+    ///
+    /// `scope` is the fully-qualified name of the package or message that contains the message.
+    fn parse_message_body<'a>(
+        message: &mut Message,
+        pairs: impl Iterator<Item = pest::iterators::Pair<'a, Rule>>,
+        scope: &str,
+    ) -> Result<(), ParserError> {
+        let message_scope = Self::qualify(scope, &message.name);
+
         for pair in pairs {
+            let before = message.definition_counts();
             match pair.as_rule() {
                 Rule::field => {
                     message.fields.push(Self::parse_field(pair)?);
                 }
+                Rule::group => {
+                    let (field, group_message) = Self::parse_group(pair, &message_scope)?;
+                    message.fields.push(field);
+                    message.nested_messages.push(group_message);
+                }
+                Rule::map_field => {
+                    message.fields.push(Self::parse_map_field(pair)?);
+                }
+                Rule::oneof_def => {
+                    let oneof =
+                        Self::parse_oneof(pair, &message_scope, &mut message.nested_messages)?;
+                    message.oneofs.push(oneof);
+                }
                 Rule::message_def => {
-                    message.nested_messages.push(Self::parse_message(pair)?);
+                    message
+                        .nested_messages
+                        .push(Self::parse_message(pair, &message_scope)?);
                 }
                 Rule::enum_def => {
                     message.nested_enums.push(Self::parse_enum(pair)?);
                 }
+                Rule::extend_def => {
+                    let extend =
+                        Self::parse_extend(pair, &message_scope, &mut message.nested_messages)?;
+                    message.extends.push(extend);
+                }
+                Rule::extensions => {
+                    message
+                        .extension_ranges
+                        .extend(Self::parse_extensions(pair)?);
+                }
+                Rule::reserved => {
+                    Self::parse_reserved(
+                        pair,
+                        &FIELD_NUMBERS,
+                        &mut message.reserved_ranges,
+                        &mut message.reserved_names,
+                    )?;
+                }
+                Rule::option_stmt => {
+                    message.options.push(Self::parse_option(pair)?);
+                }
                 _ => {}
             }
+            let after = message.definition_counts();
+            declare(&mut message.declarations, before, after);
         }
 
-        Ok(message)
+        Ok(())
+    }
+
+    /// Parses a proto2 group from a pest Pair.
+    ///
+    /// A group declares a field and a message type at once, so both are returned:
+    /// the field is named after the lowercased group name and refers to the message.
+    /// `scope` is the fully-qualified name of the package or message the group message belongs to.
+    fn parse_group(
+        pair: pest::iterators::Pair<Rule>,
+        scope: &str,
+    ) -> Result<(Field, Message), ParserError> {
+        let span = Span::from_pair(&pair);
+        let mut pairs = pair.into_inner().peekable();
+
+        let mut label = Label::Implicit;
+        if let Some(first_pair) = pairs.peek() {
+            if first_pair.as_rule() == Rule::field_rule {
+                label = Label::from_rule(first_pair.as_str());
+                pairs.next();
+            }
+        }
+
+        let name_pair = pairs.next().unwrap();
+        let name = name_pair.as_str().to_string();
+        if !name.starts_with(|c: char| c.is_ascii_uppercase()) {
+            return Err(Self::error_at(
+                &name_pair,
+                format!("group name '{}' must start with a capital letter", name),
+            ));
+        }
+
+        let tag = Self::parse_field_number(&pairs.next().unwrap())?;
+
+        let mut field = Field {
+            name: name.to_lowercase(),
+            type_name: name.clone(),
+            tag,
+            label,
+            kind: FieldKind::Group,
+            deprecated: None,
+            packed: None,
+            json_name: None,
+            default: None,
+            lazy: None,
+            debug_redact: None,
+            options: Vec::new(),
+            comments: Comments::default(),
+            span,
+        };
+
+        if pairs.peek().map(|pair| pair.as_rule()) == Some(Rule::field_options) {
+            Self::apply_field_options(&mut field, pairs.next().unwrap())?;
+        }
+
+        let mut message = Message::new(name, span);
+        Self::parse_message_body(&mut message, pairs, scope)?;
+
+        Ok((field, message))
     }
 
     /// Parses a field definition from a pest Pair.
@@ -276,14 +1032,24 @@ impl Proto {
             name: String::new(),
             type_name: String::new(),
             tag: 0,
-            repeated: false,
+            label: Label::Implicit,
+            kind: FieldKind::Normal,
+            deprecated: None,
+            packed: None,
+            json_name: None,
+            default: None,
+            lazy: None,
+            debug_redact: None,
+            options: Vec::new(),
+            comments: Comments::default(),
+            span: Span::from_pair(&pair),
         };
 
         let mut pairs = pair.into_inner().peekable();
 
         if let Some(first_pair) = pairs.peek() {
             if first_pair.as_rule() == Rule::field_rule {
-                field.repeated = first_pair.as_str() == "repeated";
+                field.label = Label::from_rule(first_pair.as_str());
                 pairs.next();
             }
         }
@@ -297,17 +1063,324 @@ impl Proto {
         }
 
         if let Some(tag_pair) = pairs.next() {
-            field.tag = tag_pair.as_str().parse().unwrap_or(0);
+            field.tag = Self::parse_field_number(&tag_pair)?;
+        }
+
+        if let Some(options_pair) = pairs.next() {
+            Self::apply_field_options(&mut field, options_pair)?;
+        }
+
+        Ok(field)
+    }
+
+    /// Parses a map field definition from a pest Pair.
+    ///
+    /// Map fields are repeated by nature, so the resulting field is always labeled as repeated.
+    fn parse_map_field(pair: pest::iterators::Pair<Rule>) -> Result<Field, ParserError> {
+        let span = Span::from_pair(&pair);
+        let mut pairs = pair.into_inner();
+
+        let key_pair = pairs.next().unwrap();
+        let key_type = key_pair.as_str().to_string();
+        if !MAP_KEY_TYPES.contains(&key_type.as_str()) {
+            return Err(Self::error_at(
+                &key_pair,
+                format!(
+                    "invalid map key type '{}', expected an integral type, bool or string",
+                    key_type
+                ),
+            ));
+        }
+
+        let value_type = pairs.next().unwrap().as_str().to_string();
+        let name = pairs.next().unwrap().as_str().to_string();
+        let tag = Self::parse_field_number(&pairs.next().unwrap())?;
+
+        let mut field = Field {
+            name,
+            type_name: format!("map<{}, {}>", key_type, value_type),
+            tag,
+            label: Label::Repeated,
+            kind: FieldKind::Map {
+                key_type,
+                value_type,
+            },
+            deprecated: None,
+            packed: None,
+            json_name: None,
+            default: None,
+            lazy: None,
+            debug_redact: None,
+            options: Vec::new(),
+            comments: Comments::default(),
+            span,
+        };
+
+        if let Some(options_pair) = pairs.next() {
+            Self::apply_field_options(&mut field, options_pair)?;
         }
 
         Ok(field)
     }
 
+    /// Applies bracketed compact options to a field.
+    fn apply_field_options(
+        field: &mut Field,
+        pair: pest::iterators::Pair<Rule>,
+    ) -> Result<(), ParserError> {
+        for option_pair in pair.into_inner() {
+            let option = Self::parse_option(option_pair.clone())?;
+            Self::apply_field_option(field, option)
+                .map_err(|message| Self::error_at(&option_pair, message))?;
+        }
+
+        Ok(())
+    }
+
+    /// Applies bracketed compact options to an enum value.
+    fn apply_enum_value_options(
+        enum_value: &mut EnumValue,
+        pair: pest::iterators::Pair<Rule>,
+    ) -> Result<(), ParserError> {
+        for option_pair in pair.into_inner() {
+            let option = Self::parse_option(option_pair.clone())?;
+            Self::apply_enum_value_option(enum_value, option)
+                .map_err(|message| Self::error_at(&option_pair, message))?;
+        }
+
+        Ok(())
+    }
+
+    /// Applies a compact option to a field.
+    ///
+    /// Well-known options are stored in their typed properties, all other options are kept as is.
+    fn apply_field_option(field: &mut Field, option: OptionDef) -> Result<(), String> {
+        match option.name.as_str() {
+            "deprecated" => Self::set_well_known(&mut field.deprecated, option, Self::bool_value),
+            "packed" => Self::set_well_known(&mut field.packed, option, Self::bool_value),
+            "json_name" => Self::set_well_known(&mut field.json_name, option, Self::string_value),
+            "default" => Self::set_well_known(&mut field.default, option, Some),
+            "lazy" => Self::set_well_known(&mut field.lazy, option, Self::bool_value),
+            "debug_redact" => {
+                Self::set_well_known(&mut field.debug_redact, option, Self::bool_value)
+            }
+            _ => {
+                field.options.push(option);
+                Ok(())
+            }
+        }
+    }
+
+    /// Applies a compact option to an enum value.
+    ///
+    /// Well-known options are stored in their typed properties, all other options are kept as is.
+    fn apply_enum_value_option(
+        enum_value: &mut EnumValue,
+        option: OptionDef,
+    ) -> Result<(), String> {
+        match option.name.as_str() {
+            "deprecated" => {
+                Self::set_well_known(&mut enum_value.deprecated, option, Self::bool_value)
+            }
+            "debug_redact" => {
+                Self::set_well_known(&mut enum_value.debug_redact, option, Self::bool_value)
+            }
+            _ => {
+                enum_value.options.push(option);
+                Ok(())
+            }
+        }
+    }
+
+    /// Stores the value of a well-known option in its typed property.
+    ///
+    /// Fails if the option was already set or if its value has an unexpected type.
+    fn set_well_known<T>(
+        target: &mut Option<T>,
+        option: OptionDef,
+        convert: fn(Constant) -> Option<T>,
+    ) -> Result<(), String> {
+        if target.is_some() {
+            return Err(format!("option '{}' is already set", option.name));
+        }
+
+        let name = option.name;
+        match convert(option.value) {
+            Some(value) => {
+                *target = Some(value);
+                Ok(())
+            }
+            None => Err(format!("option '{}' has a value of the wrong type", name)),
+        }
+    }
+
+    /// Extracts a boolean from a constant, if it is one.
+    fn bool_value(constant: Constant) -> Option<bool> {
+        match constant {
+            Constant::Bool(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Extracts a string from a constant, if it is one.
+    fn string_value(constant: Constant) -> Option<String> {
+        match constant {
+            Constant::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Parses a oneof definition from a pest Pair.
+    ///
+    /// `scope` is the fully-qualified name of the enclosing message, and messages declared
+    /// by groups inside the oneof are added to `nested_messages` of that message.
+    fn parse_oneof(
+        pair: pest::iterators::Pair<Rule>,
+        scope: &str,
+        nested_messages: &mut Vec<Message>,
+    ) -> Result<Oneof, ParserError> {
+        let mut oneof = Oneof {
+            name: String::new(),
+            fields: Vec::new(),
+            options: Vec::new(),
+            comments: Comments::default(),
+            span: Span::from_pair(&pair),
+        };
+
+        let mut pairs = pair.into_inner();
+
+        if let Some(name_pair) = pairs.next() {
+            if name_pair.as_rule() == Rule::ident {
+                oneof.name = name_pair.as_str().to_string();
+            }
+        }
+
+        for pair in pairs {
+            match pair.as_rule() {
+                Rule::oneof_field => {
+                    oneof.fields.push(Self::parse_field(pair)?);
+                }
+                Rule::group => {
+                    let (field, group_message) = Self::parse_group(pair, scope)?;
+                    oneof.fields.push(field);
+                    nested_messages.push(group_message);
+                }
+                Rule::option_stmt => {
+                    oneof.options.push(Self::parse_option(pair)?);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(oneof)
+    }
+
+    /// Parses an option statement or a single compact option from a pest Pair.
+    fn parse_option(pair: pest::iterators::Pair<Rule>) -> Result<OptionDef, ParserError> {
+        let span = Span::from_pair(&pair);
+        let mut pairs = pair.into_inner();
+
+        let name = Self::parse_option_name(pairs.next().unwrap());
+        let value = Self::parse_constant(pairs.next().unwrap())?;
+
+        Ok(OptionDef {
+            name,
+            value,
+            comments: Comments::default(),
+            span,
+        })
+    }
+
+    /// Builds the canonical name of an option, normalizing whitespace around its parts.
+    fn parse_option_name(pair: pest::iterators::Pair<Rule>) -> String {
+        pair.into_inner()
+            .map(|part| {
+                let ident = part.into_inner().next().unwrap();
+                match ident.as_rule() {
+                    Rule::type_ref => format!("({})", ident.as_str()),
+                    _ => ident.as_str().to_string(),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    /// Parses a constant value from a pest Pair.
+    fn parse_constant(pair: pest::iterators::Pair<Rule>) -> Result<Constant, ParserError> {
+        let inner = pair.into_inner().next().unwrap();
+
+        let constant = match inner.as_rule() {
+            Rule::string_lit => match String::from_utf8(Self::parse_bytes(inner)?) {
+                Ok(value) => Constant::String(value),
+                Err(error) => Constant::Bytes(error.into_bytes()),
+            },
+            Rule::signed_number => {
+                let value = Self::parse_integer(&inner)?;
+                if let Ok(value) = i64::try_from(value) {
+                    Constant::Integer(value)
+                } else if let Ok(value) = u64::try_from(value) {
+                    Constant::UnsignedInteger(value)
+                } else {
+                    return Err(Self::error_at(
+                        &inner,
+                        format!("integer constant {} is out of range", inner.as_str()),
+                    ));
+                }
+            }
+            Rule::float => Constant::Float(Self::parse_float(&inner)?),
+            Rule::aggregate => Self::parse_aggregate(inner)?,
+            _ => match inner.as_str() {
+                "true" => Constant::Bool(true),
+                "false" => Constant::Bool(false),
+                ident => Constant::Identifier(ident.to_string()),
+            },
+        };
+
+        Ok(constant)
+    }
+
+    /// Parses an aggregate constant written in protobuf text format from a pest Pair.
+    fn parse_aggregate(pair: pest::iterators::Pair<Rule>) -> Result<Constant, ParserError> {
+        let mut fields = Vec::new();
+
+        for field_pair in pair.into_inner() {
+            let mut pairs = field_pair.into_inner();
+
+            let name_pair = pairs.next().unwrap().into_inner().next().unwrap();
+            let name = match name_pair.as_rule() {
+                Rule::full_ident => format!("[{}]", name_pair.as_str()),
+                _ => name_pair.as_str().to_string(),
+            };
+
+            let value_pair = pairs.next().unwrap();
+            let value = match value_pair.as_rule() {
+                Rule::aggregate => Self::parse_aggregate(value_pair)?,
+                Rule::list => Constant::List(
+                    value_pair
+                        .into_inner()
+                        .map(Self::parse_constant)
+                        .collect::<Result<_, _>>()?,
+                ),
+                _ => Self::parse_constant(value_pair)?,
+            };
+
+            fields.push(AggregateField { name, value });
+        }
+
+        Ok(Constant::Aggregate(fields))
+    }
+
     /// Parses an enum definition from a pest Pair.
     fn parse_enum(pair: pest::iterators::Pair<Rule>) -> Result<EnumDef, ParserError> {
         let mut enum_def = EnumDef {
             name: String::new(),
             values: Vec::new(),
+            reserved_ranges: Vec::new(),
+            reserved_names: Vec::new(),
+            options: Vec::new(),
+            aliases: Vec::new(),
+            comments: Comments::default(),
+            span: Span::from_pair(&pair),
         };
 
         let mut pairs = pair.into_inner();
@@ -319,32 +1392,291 @@ impl Proto {
         }
 
         for pair in pairs {
-            if pair.as_rule() == Rule::enum_value {
-                let mut value_pairs = pair.into_inner();
-                let mut enum_value = EnumValue {
-                    name: String::new(),
-                    number: 0,
-                };
+            match pair.as_rule() {
+                Rule::enum_value => {
+                    let mut enum_value = EnumValue {
+                        name: String::new(),
+                        number: 0,
+                        deprecated: None,
+                        debug_redact: None,
+                        options: Vec::new(),
+                        comments: Comments::default(),
+                        span: Span::from_pair(&pair),
+                    };
+                    let mut value_pairs = pair.into_inner();
+
+                    if let Some(name_pair) = value_pairs.next() {
+                        enum_value.name = name_pair.as_str().to_string();
+                    }
+                    if let Some(number_pair) = value_pairs.next() {
+                        enum_value.number = Self::parse_number_in(&number_pair, &ENUM_NUMBERS)?;
+                    }
+                    if let Some(options_pair) = value_pairs.next() {
+                        Self::apply_enum_value_options(&mut enum_value, options_pair)?;
+                    }
 
-                if let Some(name_pair) = value_pairs.next() {
-                    enum_value.name = name_pair.as_str().to_string();
+                    enum_def.values.push(enum_value);
                 }
-                if let Some(number_pair) = value_pairs.next() {
-                    enum_value.number = number_pair.as_str().parse().unwrap_or(0);
+                Rule::reserved => {
+                    Self::parse_reserved(
+                        pair,
+                        &ENUM_NUMBERS,
+                        &mut enum_def.reserved_ranges,
+                        &mut enum_def.reserved_names,
+                    )?;
                 }
-
-                enum_def.values.push(enum_value);
+                Rule::option_stmt => {
+                    enum_def.options.push(Self::parse_option(pair)?);
+                }
+                _ => {}
             }
         }
 
+        enum_def.aliases = Self::collect_aliases(&enum_def)?;
+
         Ok(enum_def)
     }
 
+    /// Finds the values of an enum that reuse the number of an earlier value.
+    ///
+    /// Aliases are only allowed when the enum sets `option allow_alias = true;`,
+    /// otherwise a duplicated number is reported as an error at the value that repeats it.
+    fn collect_aliases(enum_def: &EnumDef) -> Result<Vec<EnumAlias>, ParserError> {
+        let allow_alias = enum_def.options.iter().any(|option| {
+            option.name == "allow_alias" && matches!(option.value, Constant::Bool(true))
+        });
+
+        let mut targets: BTreeMap<i32, &str> = BTreeMap::new();
+        let mut aliases = Vec::new();
+
+        for value in &enum_def.values {
+            let Some(target) = targets.get(&value.number) else {
+                targets.insert(value.number, &value.name);
+                continue;
+            };
+            if !allow_alias {
+                let mut message = format!(
+                    "'{}' uses the same number {} as '{}' in enum '{}', \
+                     set 'option allow_alias = true;' to allow aliases",
+                    value.name, value.number, target, enum_def.name
+                );
+                if !value.span.is_unknown() {
+                    let span = value.span;
+                    message +=
+                        &format!(" at line {}, column {}", span.start_line, span.start_column);
+                }
+                return Err(ParserError::ValidationError(message));
+            }
+            aliases.push(EnumAlias {
+                name: value.name.clone(),
+                target: target.to_string(),
+                number: value.number,
+            });
+        }
+
+        Ok(aliases)
+    }
+
+    /// Parses an extend block from a pest Pair.
+    ///
+    /// `scope` is the fully-qualified name of the package or message that contains the block,
+    /// and messages declared by groups inside the block are added to `messages` of that scope.
+    fn parse_extend(
+        pair: pest::iterators::Pair<Rule>,
+        scope: &str,
+        messages: &mut Vec<Message>,
+    ) -> Result<Extend, ParserError> {
+        let span = Span::from_pair(&pair);
+        let mut pairs = pair.into_inner();
+
+        let mut extend = Extend {
+            extendee: pairs.next().unwrap().as_str().to_string(),
+            fields: Vec::new(),
+            scope: scope.to_string(),
+            comments: Comments::default(),
+            span,
+        };
+
+        for pair in pairs {
+            match pair.as_rule() {
+                Rule::field => {
+                    extend.fields.push(Self::parse_field(pair)?);
+                }
+                Rule::group => {
+                    let (field, group_message) = Self::parse_group(pair, scope)?;
+                    extend.fields.push(field);
+                    messages.push(group_message);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(extend)
+    }
+
+    /// Parses an extensions statement from a pest Pair into one entry per declared range.
+    fn parse_extensions(
+        pair: pest::iterators::Pair<Rule>,
+    ) -> Result<Vec<ExtensionRange>, ParserError> {
+        let mut pairs = pair.into_inner();
+
+        let ranges = pairs
+            .next()
+            .unwrap()
+            .into_inner()
+            .map(|range_pair| Self::parse_range(range_pair, &FIELD_NUMBERS))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut options = Vec::new();
+        if let Some(options_pair) = pairs.next() {
+            for option_pair in options_pair.into_inner() {
+                options.push(Self::parse_option(option_pair)?);
+            }
+        }
+
+        let extension_ranges = ranges
+            .into_iter()
+            .map(|range| ExtensionRange {
+                start: range.start,
+                end: range.end,
+                options: options.clone(),
+                span: range.span,
+            })
+            .collect();
+
+        Ok(extension_ranges)
+    }
+
+    /// Parses a reserved statement from a pest Pair into the given range and name lists.
+    ///
+    /// `bounds` are the numbers allowed in the enclosing definition; `max` stands for its end.
+    fn parse_reserved(
+        pair: pest::iterators::Pair<Rule>,
+        bounds: &RangeInclusive<i32>,
+        ranges: &mut Vec<Range>,
+        names: &mut Vec<String>,
+    ) -> Result<(), ParserError> {
+        let inner = pair.into_inner().next().unwrap();
+
+        match inner.as_rule() {
+            Rule::ranges => {
+                for range_pair in inner.into_inner() {
+                    ranges.push(Self::parse_range(range_pair, bounds)?);
+                }
+            }
+            _ => {
+                for name_pair in inner.into_inner() {
+                    let name = match name_pair.as_rule() {
+                        Rule::string_lit => Self::parse_string(name_pair)?,
+                        _ => name_pair.as_str().to_string(),
+                    };
+                    names.push(name);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parses a number range (e.g. `2`, `9 to 11` or `100 to max`) from a pest Pair.
+    fn parse_range(
+        pair: pest::iterators::Pair<Rule>,
+        bounds: &RangeInclusive<i32>,
+    ) -> Result<Range, ParserError> {
+        let range_pair = pair.clone();
+        let mut pairs = pair.into_inner();
+
+        let start = Self::parse_number_in(&pairs.next().unwrap(), bounds)?;
+        let end = match pairs.next() {
+            Some(end_pair) if end_pair.as_rule() == Rule::max => *bounds.end(),
+            Some(end_pair) => Self::parse_number_in(&end_pair, bounds)?,
+            None => start,
+        };
+
+        if end < start {
+            return Err(Self::error_at(
+                &range_pair,
+                format!("range end {} is smaller than range start {}", end, start),
+            ));
+        }
+
+        Ok(Range {
+            start,
+            end,
+            span: Span::from_pair(&range_pair),
+        })
+    }
+
+    /// Parses a field number, which must lie between 1 and 536870911.
+    fn parse_field_number(pair: &pest::iterators::Pair<Rule>) -> Result<i32, ParserError> {
+        Self::parse_number_in(pair, &FIELD_NUMBERS)
+    }
+
+    /// Parses an integer literal that must lie within the given bounds.
+    fn parse_number_in(
+        pair: &pest::iterators::Pair<Rule>,
+        bounds: &RangeInclusive<i32>,
+    ) -> Result<i32, ParserError> {
+        Self::parse_integer(pair)?
+            .try_into()
+            .ok()
+            .filter(|number| bounds.contains(number))
+            .ok_or_else(|| {
+                Self::error_at(
+                    pair,
+                    format!(
+                        "number {} is out of range, expected {} to {}",
+                        pair.as_str(),
+                        bounds.start(),
+                        bounds.end()
+                    ),
+                )
+            })
+    }
+
+    /// Parses a decimal, octal (`017`) or hexadecimal (`0x1F`) integer literal
+    /// with an optional sign from a pest Pair.
+    fn parse_integer(pair: &pest::iterators::Pair<Rule>) -> Result<i128, ParserError> {
+        let text = pair.as_str();
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+
+        let value = if let Some(hex) = digits
+            .strip_prefix("0x")
+            .or_else(|| digits.strip_prefix("0X"))
+        {
+            u64::from_str_radix(hex, 16)
+        } else if digits.len() > 1 && digits.starts_with('0') {
+            u64::from_str_radix(&digits[1..], 8)
+        } else {
+            digits.parse()
+        }
+        .map_err(|_| Self::error_at(pair, format!("integer literal {} is out of range", text)))?;
+
+        Ok(if negative {
+            -i128::from(value)
+        } else {
+            i128::from(value)
+        })
+    }
+
+    /// Parses a floating-point literal (e.g. `1.5e3`, `-inf` or `nan`) from a pest Pair.
+    fn parse_float(pair: &pest::iterators::Pair<Rule>) -> Result<f64, ParserError> {
+        pair.as_str()
+            .parse()
+            .map_err(|_| Self::error_at(pair, format!("invalid float literal {}", pair.as_str())))
+    }
+
     /// Parses a service definition from a pest Pair.
     fn parse_service(pair: pest::iterators::Pair<Rule>) -> Result<Service, ParserError> {
         let mut service = Service {
             name: String::new(),
             methods: Vec::new(),
+            options: Vec::new(),
+            comments: Comments::default(),
+            span: Span::from_pair(&pair),
         };
 
         let mut pairs = pair.into_inner();
@@ -356,11 +1688,18 @@ impl Proto {
         }
 
         for pair in pairs {
-            if pair.as_rule() == Rule::rpc_def {
+            if pair.as_rule() == Rule::option_stmt {
+                service.options.push(Self::parse_option(pair)?);
+            } else if pair.as_rule() == Rule::rpc_def {
                 let mut method = Method {
                     name: String::new(),
                     input_type: String::new(),
                     output_type: String::new(),
+                    client_streaming: false,
+                    server_streaming: false,
+                    options: Vec::new(),
+                    comments: Comments::default(),
+                    span: Span::from_pair(&pair),
                 };
 
                 let mut rpc_pairs = pair.into_inner();
@@ -369,10 +1708,15 @@ impl Proto {
                     method.name = name_pair.as_str().to_string();
                 }
                 if let Some(input_pair) = rpc_pairs.next() {
-                    method.input_type = input_pair.into_inner().next().unwrap().as_str().to_string();
+                    (method.input_type, method.client_streaming) =
+                        Self::parse_message_type(input_pair);
                 }
                 if let Some(output_pair) = rpc_pairs.next() {
-                    method.output_type = output_pair.into_inner().next().unwrap().as_str().to_string();
+                    (method.output_type, method.server_streaming) =
+                        Self::parse_message_type(output_pair);
+                }
+                for option_pair in rpc_pairs {
+                    method.options.push(Self::parse_option(option_pair)?);
                 }
 
                 service.methods.push(method);
@@ -381,4 +1725,144 @@ impl Proto {
 
         Ok(service)
     }
-}
\ No newline at end of file
+
+    /// Parses the input or output type of an RPC method from a pest Pair.
+    ///
+    /// Returns the type name and whether it is marked with `stream`.
+    fn parse_message_type(pair: pest::iterators::Pair<Rule>) -> (String, bool) {
+        let mut streaming = false;
+        let mut type_name = String::new();
+
+        for inner_pair in pair.into_inner() {
+            match inner_pair.as_rule() {
+                Rule::stream => streaming = true,
+                _ => type_name = inner_pair.as_str().to_string(),
+            }
+        }
+
+        (type_name, streaming)
+    }
+
+    /// Parses a string literal from a pest Pair that must decode to valid UTF-8.
+    fn parse_string(pair: pest::iterators::Pair<Rule>) -> Result<String, ParserError> {
+        let bytes = Self::parse_bytes(pair.clone())?;
+
+        String::from_utf8(bytes)
+            .map_err(|_| Self::error_at(&pair, "string literal is not valid UTF-8".to_string()))
+    }
+
+    /// Parses a string literal from a pest Pair, decoding escape sequences
+    /// and concatenating adjacent literals (e.g. `"abc" 'def'`).
+    fn parse_bytes(pair: pest::iterators::Pair<Rule>) -> Result<Vec<u8>, ParserError> {
+        let mut bytes = Vec::new();
+
+        for part in pair.into_inner() {
+            let content = part.clone().into_inner().next().unwrap().as_str();
+            Self::decode_escapes(content, &mut bytes)
+                .map_err(|message| Self::error_at(&part, message))?;
+        }
+
+        Ok(bytes)
+    }
+
+    /// Decodes the content of a single string literal into bytes.
+    ///
+    /// Supports simple character escapes as well as hex (`\x41`), octal (`\101`)
+    /// and unicode (`\u00e9`, `\U0001F600`) escapes.
+    fn decode_escapes(content: &str, bytes: &mut Vec<u8>) -> Result<(), String> {
+        let mut chars = content.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                let mut buffer = [0; 4];
+                bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+                continue;
+            }
+
+            let escape = chars
+                .next()
+                .ok_or_else(|| "unterminated escape sequence in string literal".to_string())?;
+            match escape {
+                'a' => bytes.push(0x07),
+                'b' => bytes.push(0x08),
+                'f' => bytes.push(0x0c),
+                'n' => bytes.push(b'\n'),
+                'r' => bytes.push(b'\r'),
+                't' => bytes.push(b'\t'),
+                'v' => bytes.push(0x0b),
+                '\\' | '\'' | '"' | '?' => bytes.push(escape as u8),
+                'x' | 'X' => {
+                    let digits = Self::take_digits(&mut chars, 16, 2);
+                    if digits.is_empty() {
+                        return Err("\\x escape must be followed by hex digits".to_string());
+                    }
+                    bytes.push(u8::from_str_radix(&digits, 16).unwrap());
+                }
+                '0'..='7' => {
+                    let digits = escape.to_string() + &Self::take_digits(&mut chars, 8, 2);
+                    let value = u32::from_str_radix(&digits, 8).unwrap();
+                    let byte = u8::try_from(value)
+                        .map_err(|_| format!("octal escape \\{} is out of range", digits))?;
+                    bytes.push(byte);
+                }
+                'u' | 'U' => {
+                    let length = if escape == 'u' { 4 } else { 8 };
+                    let digits = Self::take_digits(&mut chars, 16, length);
+                    if digits.len() != length {
+                        return Err(format!(
+                            "\\{} escape must be followed by {} hex digits",
+                            escape, length
+                        ));
+                    }
+                    let c = char::from_u32(u32::from_str_radix(&digits, 16).unwrap()).ok_or_else(
+                        || format!("\\{}{} is not a valid unicode code point", escape, digits),
+                    )?;
+                    let mut buffer = [0; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+                }
+                other => {
+                    return Err(format!(
+                        "invalid escape sequence '\\{}' in string literal",
+                        other
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Consumes up to `max` digits of the given radix from the iterator.
+    fn take_digits(
+        chars: &mut std::iter::Peekable<std::str::Chars>,
+        radix: u32,
+        max: usize,
+    ) -> String {
+        let mut digits = String::new();
+        while digits.len() < max {
+            match chars.peek() {
+                Some(c) if c.is_digit(radix) => {
+                    digits.push(*c);
+                    chars.next();
+                }
+                _ => break,
+            }
+        }
+        digits
+    }
+
+    /// Joins a scope and a name into a fully-qualified name.
+    fn qualify(scope: &str, name: &str) -> String {
+        if scope.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{}", scope, name)
+        }
+    }
+
+    /// Builds a syntax error that points at the position of the given pair.
+    fn error_at(pair: &pest::iterators::Pair<Rule>, message: String) -> ParserError {
+        let (line, column) = pair.as_span().start_pos().line_col();
+        ParserError::SyntaxError(format!("{} at line {}, column {}", message, line, column))
+    }
+}
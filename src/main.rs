@@ -10,6 +10,7 @@ Commands:
     Options:
       -o, --output <FILE>   Output file (optional, defaults to stdout)
       -p, --pretty         Pretty print the JSON output
+      -s, --spans          Include source locations of definitions
 
   help                     Show grammar guide and usage information
   credits                  Show project credits and information
@@ -21,6 +22,9 @@ Examples:
   Parse and save as pretty-printed JSON:
     proto-file-parser parse input.proto -p -o output.json
 
+  Parse and include source locations:
+    proto-file-parser parse input.proto --spans
+
   Show credits:
     proto-file-parser credits
 
@@ -51,6 +55,7 @@ enum Command {
         file: PathBuf,
         output: Option<PathBuf>,
         pretty: bool,
+        spans: bool,
     },
     Help,
     Credits,
@@ -72,6 +77,7 @@ fn parse_args() -> Result<Command, String> {
             }
             let mut output = None;
             let mut pretty = false;
+            let mut spans = false;
             let mut i = 2;
             while i < args.len() {
                 match args[i].as_str() {
@@ -86,6 +92,10 @@ fn parse_args() -> Result<Command, String> {
                         pretty = true;
                         i += 1;
                     }
+                    "-s" | "--spans" => {
+                        spans = true;
+                        i += 1;
+                    }
                     _ => {
                         return Err(format!("Unknown option: {}", args[i]));
                     }
@@ -95,6 +105,7 @@ fn parse_args() -> Result<Command, String> {
                 file: PathBuf::from(&args[1]),
                 output,
                 pretty,
+                spans,
             })
         }
         cmd => Err(format!("Unknown command: {}. Use 'help' for usage information.", cmd)),
@@ -119,8 +130,20 @@ fn main() {
     };
 
     match command {
-        Command::Parse { file, output, pretty } => {
-            match Proto::parse_file(file.to_str().unwrap()) {
+        Command::Parse {
+            file,
+            output,
+            pretty,
+            spans,
+        } => {
+            let json = Proto::parse_file(file.to_str().unwrap()).and_then(|proto| {
+                if spans {
+                    proto.to_json_with_spans()
+                } else {
+                    proto.to_json()
+                }
+            });
+            match json {
                 Ok(json) => {
                     let result = if pretty {
                         match serde_json::from_str::<serde_json::Value>(&json) {
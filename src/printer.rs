@@ -0,0 +1,625 @@
+//! Rendering of proto files as `.proto` source.
+
+use crate::builder::is_ident;
+use crate::{
+    Comments, Constant, Declaration, DeclarationKind, EnumDef, Extend, ExtensionRange, Field,
+    FieldKind, ImportModifier, Label, Message, Method, Oneof, OptionDef, Proto, Range, Service,
+    MAX_FIELD_NUMBER,
+};
+use std::collections::HashSet;
+use std::fmt::Write;
+
+/// Indentation of one nesting level.
+const INDENT: &str = "  ";
+
+impl Proto {
+    /// Renders the proto definition as `.proto` source.
+    ///
+    /// The output is laid out canonically: the syntax or edition comes first, followed by the
+    /// package, imports and file options, and then the definitions in the order they were
+    /// declared. Bodies are indented by two spaces, with options first, then the declared
+    /// definitions, extension ranges and reserved ranges and names. Comments are written so
+    /// that they attach to the same definitions again, and parsing the output yields a
+    /// definition equal to this one apart from its spans. The only exception are block comments
+    /// containing `*/`, which are written as line comments and so read back ending with a newline.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use proto_file_parser::Proto;
+    ///
+    /// let proto = Proto::parse("syntax = 'proto3'; message User { string name = 1; }").unwrap();
+    /// assert_eq!(
+    ///     proto.to_proto(),
+    ///     "syntax = \"proto3\";\n\nmessage User {\n  string name = 1;\n}\n"
+    /// );
+    /// assert!(Proto::parse(&proto.to_proto()).unwrap().eq_ignoring_spans(&proto));
+    /// ```
+    pub fn to_proto(&self) -> String {
+        let mut printer = Printer {
+            out: String::new(),
+            depth: 0,
+            reserved_idents: self.edition.is_some(),
+            blank: false,
+            after_body: false,
+            scope_start: false,
+            collapsible: false,
+        };
+        printer.file(self);
+        printer.out
+    }
+}
+
+/// Writes definitions as `.proto` source.
+struct Printer {
+    /// Source written so far
+    out: String,
+    /// Current nesting level
+    depth: usize,
+    /// Indicates if reserved names are written as identifiers, as editions require
+    reserved_idents: bool,
+    /// Indicates if the next statement must follow a blank line, so that it does not
+    /// take over a trailing comment written below the previous statement
+    blank: bool,
+    /// Indicates if the previous statement had a body
+    after_body: bool,
+    /// Indicates if nothing was written since the current body was opened
+    scope_start: bool,
+    /// Indicates if the current body can be closed on the line that opened it
+    collapsible: bool,
+}
+
+impl Printer {
+    fn file(&mut self, proto: &Proto) {
+        match &proto.edition {
+            Some(edition) => self.line(&format!("edition = {};", quote(edition))),
+            None => self.line(&format!("syntax = {};", quote(&proto.syntax))),
+        }
+        if let Some(package) = &proto.package {
+            self.begin(&Comments::default(), true);
+            self.line(&format!("package {};", package));
+        }
+        for (i, import) in proto.imports.iter().enumerate() {
+            let modifier = match import.modifier {
+                Some(ImportModifier::Public) => "public ",
+                Some(ImportModifier::Weak) => "weak ",
+                None => "",
+            };
+            self.begin(&import.comments, i == 0);
+            self.end(
+                &format!("import {}{};", modifier, quote(&import.path)),
+                &import.comments,
+            );
+        }
+        for (i, option) in proto.options.iter().enumerate() {
+            self.option(option, i == 0);
+        }
+
+        let mut messages = Messages::new(&proto.messages);
+        for declaration in declaration_order(&proto.declarations, &proto.definition_counts()) {
+            match declaration.kind {
+                DeclarationKind::Message => {
+                    if let Some(message) = messages.take(declaration.index) {
+                        self.message(message);
+                    }
+                }
+                DeclarationKind::Enum => self.enum_def(&proto.enums[declaration.index]),
+                DeclarationKind::Service => self.service(&proto.services[declaration.index]),
+                DeclarationKind::Extend => {
+                    self.extend(&proto.extends[declaration.index], &mut messages)
+                }
+                DeclarationKind::Field | DeclarationKind::Oneof => {}
+            }
+        }
+    }
+
+    fn message(&mut self, message: &Message) {
+        self.begin(&message.comments, true);
+        self.open(&format!("message {}", message.name), &message.comments);
+        self.message_body(message);
+        self.close();
+    }
+
+    fn message_body(&mut self, message: &Message) {
+        self.options(&message.options);
+
+        let mut messages = Messages::new(&message.nested_messages);
+        for declaration in declaration_order(&message.declarations, &message.definition_counts()) {
+            match declaration.kind {
+                DeclarationKind::Field => {
+                    self.field(&message.fields[declaration.index], &mut messages)
+                }
+                DeclarationKind::Oneof => {
+                    self.oneof(&message.oneofs[declaration.index], &mut messages)
+                }
+                DeclarationKind::Message => {
+                    if let Some(nested) = messages.take(declaration.index) {
+                        self.message(nested);
+                    }
+                }
+                DeclarationKind::Enum => self.enum_def(&message.nested_enums[declaration.index]),
+                DeclarationKind::Extend => {
+                    self.extend(&message.extends[declaration.index], &mut messages)
+                }
+                DeclarationKind::Service => {}
+            }
+        }
+
+        self.extension_ranges(&message.extension_ranges);
+        self.reserved(
+            &message.reserved_ranges,
+            &message.reserved_names,
+            MAX_FIELD_NUMBER,
+        );
+    }
+
+    /// Writes a field, taking the body of a group from the messages of the enclosing scope.
+    fn field(&mut self, field: &Field, messages: &mut Messages) {
+        let label = match field.label {
+            Label::Implicit => "",
+            Label::Optional => "optional ",
+            Label::Required => "required ",
+            Label::Repeated => "repeated ",
+        };
+        let options = field_options(field);
+
+        match &field.kind {
+            FieldKind::Normal => {
+                self.begin(&field.comments, false);
+                self.end(
+                    &format!(
+                        "{}{} {} = {}{};",
+                        label, field.type_name, field.name, field.tag, options
+                    ),
+                    &field.comments,
+                );
+            }
+            FieldKind::Map {
+                key_type,
+                value_type,
+            } => {
+                self.begin(&field.comments, false);
+                self.end(
+                    &format!(
+                        "map<{}, {}> {} = {}{};",
+                        key_type, value_type, field.name, field.tag, options
+                    ),
+                    &field.comments,
+                );
+            }
+            FieldKind::Group => {
+                self.begin(&field.comments, true);
+                self.open(
+                    &format!(
+                        "{}group {} = {}{}",
+                        label, field.type_name, field.tag, options
+                    ),
+                    &field.comments,
+                );
+                if let Some(message) = messages.take_group(&field.type_name) {
+                    self.message_body(message);
+                }
+                self.close();
+            }
+        }
+    }
+
+    fn oneof(&mut self, oneof: &Oneof, messages: &mut Messages) {
+        self.begin(&oneof.comments, true);
+        self.open(&format!("oneof {}", oneof.name), &oneof.comments);
+        self.options(&oneof.options);
+        for field in &oneof.fields {
+            self.field(field, messages);
+        }
+        self.close();
+    }
+
+    fn enum_def(&mut self, enum_def: &EnumDef) {
+        self.begin(&enum_def.comments, true);
+        self.open(&format!("enum {}", enum_def.name), &enum_def.comments);
+        self.options(&enum_def.options);
+        for value in &enum_def.values {
+            let mut options = Vec::new();
+            if let Some(deprecated) = value.deprecated {
+                options.push(format!("deprecated = {}", deprecated));
+            }
+            if let Some(debug_redact) = value.debug_redact {
+                options.push(format!("debug_redact = {}", debug_redact));
+            }
+            options.extend(value.options.iter().map(compact_option));
+
+            self.begin(&value.comments, false);
+            self.end(
+                &format!("{} = {}{};", value.name, value.number, brackets(&options)),
+                &value.comments,
+            );
+        }
+        self.reserved(
+            &enum_def.reserved_ranges,
+            &enum_def.reserved_names,
+            i32::MAX,
+        );
+        self.close();
+    }
+
+    fn service(&mut self, service: &Service) {
+        self.begin(&service.comments, true);
+        self.open(&format!("service {}", service.name), &service.comments);
+        self.options(&service.options);
+        for method in &service.methods {
+            self.method(method);
+        }
+        self.close();
+    }
+
+    fn method(&mut self, method: &Method) {
+        let stream = |streaming| if streaming { "stream " } else { "" };
+        let header = format!(
+            "rpc {} ({}{}) returns ({}{})",
+            method.name,
+            stream(method.client_streaming),
+            method.input_type,
+            stream(method.server_streaming),
+            method.output_type
+        );
+
+        if method.options.is_empty() {
+            self.begin(&method.comments, false);
+            self.end(&format!("{};", header), &method.comments);
+        } else {
+            self.begin(&method.comments, true);
+            self.open(&header, &method.comments);
+            self.options(&method.options);
+            self.close();
+        }
+    }
+
+    fn extend(&mut self, extend: &Extend, messages: &mut Messages) {
+        self.begin(&extend.comments, true);
+        self.open(&format!("extend {}", extend.extendee), &extend.comments);
+        for field in &extend.fields {
+            self.field(field, messages);
+        }
+        self.close();
+    }
+
+    fn options(&mut self, options: &[OptionDef]) {
+        for option in options {
+            self.option(option, false);
+        }
+    }
+
+    fn option(&mut self, option: &OptionDef, separate: bool) {
+        self.begin(&option.comments, separate);
+        self.end(
+            &format!("option {} = {};", option.name, constant(&option.value)),
+            &option.comments,
+        );
+    }
+
+    /// Writes extension ranges, joining consecutive ranges with the same options.
+    fn extension_ranges(&mut self, ranges: &[ExtensionRange]) {
+        for group in ranges.chunk_by(|a, b| a.options == b.options) {
+            let numbers: Vec<_> = group
+                .iter()
+                .map(|range| number_range(range.start, range.end, MAX_FIELD_NUMBER))
+                .collect();
+            let options: Vec<_> = group[0].options.iter().map(compact_option).collect();
+
+            self.begin(&Comments::default(), false);
+            self.line(&format!(
+                "extensions {}{};",
+                numbers.join(", "),
+                brackets(&options)
+            ));
+        }
+    }
+
+    /// Writes reserved ranges and names; `max` is the number written as `max`.
+    fn reserved(&mut self, ranges: &[Range], names: &[String], max: i32) {
+        if !ranges.is_empty() {
+            let numbers: Vec<_> = ranges
+                .iter()
+                .map(|range| number_range(range.start, range.end, max))
+                .collect();
+            self.begin(&Comments::default(), false);
+            self.line(&format!("reserved {};", numbers.join(", ")));
+        }
+        if !names.is_empty() {
+            let names: Vec<_> = names
+                .iter()
+                .map(|name| {
+                    if self.reserved_idents && is_ident(name) {
+                        name.clone()
+                    } else {
+                        quote(name)
+                    }
+                })
+                .collect();
+            self.begin(&Comments::default(), false);
+            self.line(&format!("reserved {};", names.join(", ")));
+        }
+    }
+
+    /// Starts a statement, writing the blank line that separates it from the previous one
+    /// and its detached and leading comments.
+    ///
+    /// `separate` asks for a blank line, which is also written after a statement with a body.
+    fn begin(&mut self, comments: &Comments, separate: bool) {
+        let separate = (separate || self.after_body) && !self.scope_start;
+        if separate || self.blank || !comments.leading_detached.is_empty() {
+            self.blank_line();
+        }
+        for detached in &comments.leading_detached {
+            self.comment(detached);
+            self.blank_line();
+        }
+        if let Some(leading) = &comments.leading {
+            self.comment(leading);
+        }
+
+        self.blank = false;
+        self.after_body = false;
+        self.scope_start = false;
+    }
+
+    /// Ends a statement with its trailing comment, on the same line if the comment has a single
+    /// line and otherwise on the lines below, followed by a blank line.
+    fn end(&mut self, text: &str, comments: &Comments) {
+        match &comments.trailing {
+            Some(trailing) if trailing.trim_end_matches('\n').contains('\n') => {
+                self.line(text);
+                // Comments below the opening brace of a body are indented like the body
+                let opens_body = text.ends_with('{');
+                self.depth += usize::from(opens_body);
+                self.comment(trailing);
+                self.depth -= usize::from(opens_body);
+                self.blank = true;
+            }
+            Some(trailing) if !is_block_comment(trailing) => {
+                self.line(&format!("{} //{}", text, trailing.trim_end_matches('\n')));
+            }
+            Some(trailing) => {
+                let comment = self.block_comment(trailing);
+                self.line(&format!("{} {}", text, comment));
+            }
+            None => self.line(text),
+        }
+    }
+
+    /// Opens the body of a statement.
+    fn open(&mut self, header: &str, comments: &Comments) {
+        self.end(&format!("{} {{", header), comments);
+        self.depth += 1;
+        self.scope_start = true;
+        self.collapsible = comments.trailing.is_none();
+    }
+
+    /// Closes the current body, on the line that opened it if the body is empty.
+    fn close(&mut self) {
+        self.depth -= 1;
+        if self.scope_start && self.collapsible {
+            self.out.pop();
+            self.out.push_str("}\n");
+        } else {
+            self.line("}");
+        }
+        self.blank = false;
+        self.after_body = true;
+        self.scope_start = false;
+    }
+
+    /// Writes a comment on lines of its own, as a block comment if [`is_block_comment`] allows
+    /// it and as line comments otherwise.
+    fn comment(&mut self, text: &str) {
+        if is_block_comment(text) {
+            let comment = self.block_comment(text);
+            self.line(&comment);
+        } else {
+            for line in text.lines() {
+                self.line(&format!("//{}", line));
+            }
+        }
+    }
+
+    /// Formats a block comment, starting the lines after the first with ` *`.
+    fn block_comment(&self, text: &str) -> String {
+        let indent = INDENT.repeat(self.depth);
+        let mut comment = String::from("/*");
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                let _ = write!(comment, "\n{} *", indent);
+            }
+            comment.push_str(line);
+        }
+        comment.push_str("*/");
+        comment
+    }
+
+    fn blank_line(&mut self) {
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+
+    fn line(&mut self, text: &str) {
+        for _ in 0..self.depth {
+            self.out.push_str(INDENT);
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+}
+
+/// Indicates if comment text is written as a block comment: it was one if it does not end with
+/// a newline, but text containing `*/` would end the block early and is written as line comments.
+fn is_block_comment(text: &str) -> bool {
+    !text.ends_with('\n') && !text.contains("*/")
+}
+
+/// Messages of a scope, keeping track of the ones already written, either on their own
+/// or as the body of a group.
+struct Messages<'a> {
+    messages: &'a [Message],
+    written: Vec<bool>,
+}
+
+impl<'a> Messages<'a> {
+    fn new(messages: &'a [Message]) -> Self {
+        Messages {
+            messages,
+            written: vec![false; messages.len()],
+        }
+    }
+
+    /// Returns the message at `index` unless it was already written.
+    fn take(&mut self, index: usize) -> Option<&'a Message> {
+        if std::mem::replace(&mut self.written[index], true) {
+            None
+        } else {
+            Some(&self.messages[index])
+        }
+    }
+
+    /// Returns the first message named `name` that was not written yet.
+    fn take_group(&mut self, name: &str) -> Option<&'a Message> {
+        let index = (0..self.messages.len())
+            .find(|&index| !self.written[index] && self.messages[index].name == name)?;
+        self.take(index)
+    }
+}
+
+/// Returns the definitions in the order to write them: the recorded declarations that refer
+/// to existing definitions, followed by the definitions missing from them, grouped by kind.
+fn declaration_order(
+    declarations: &[Declaration],
+    counts: &[(DeclarationKind, usize)],
+) -> Vec<Declaration> {
+    let mut seen = HashSet::new();
+    let recorded = declarations.iter().copied().filter(|declaration| {
+        counts
+            .iter()
+            .any(|&(kind, count)| kind == declaration.kind && declaration.index < count)
+    });
+    let all = counts
+        .iter()
+        .flat_map(|&(kind, count)| (0..count).map(move |index| Declaration { kind, index }));
+
+    recorded
+        .chain(all)
+        .filter(|declaration| seen.insert(*declaration))
+        .collect()
+}
+
+/// Formats the compact options of a field, well-known options first.
+fn field_options(field: &Field) -> String {
+    let mut options = Vec::new();
+    if let Some(deprecated) = field.deprecated {
+        options.push(format!("deprecated = {}", deprecated));
+    }
+    if let Some(packed) = field.packed {
+        options.push(format!("packed = {}", packed));
+    }
+    if let Some(json_name) = &field.json_name {
+        options.push(format!("json_name = {}", quote(json_name)));
+    }
+    if let Some(default) = &field.default {
+        options.push(format!("default = {}", constant(default)));
+    }
+    if let Some(lazy) = field.lazy {
+        options.push(format!("lazy = {}", lazy));
+    }
+    if let Some(debug_redact) = field.debug_redact {
+        options.push(format!("debug_redact = {}", debug_redact));
+    }
+    options.extend(field.options.iter().map(compact_option));
+    brackets(&options)
+}
+
+fn compact_option(option: &OptionDef) -> String {
+    format!("{} = {}", option.name, constant(&option.value))
+}
+
+/// Formats compact options as ` [a = 1, b = 2]`, or nothing if there are none.
+fn brackets(options: &[String]) -> String {
+    if options.is_empty() {
+        String::new()
+    } else {
+        format!(" [{}]", options.join(", "))
+    }
+}
+
+/// Formats a number range, writing `max` for its upper bound.
+fn number_range(start: i32, end: i32, max: i32) -> String {
+    match end {
+        _ if end == start => start.to_string(),
+        _ if end == max => format!("{} to max", start),
+        _ => format!("{} to {}", start, end),
+    }
+}
+
+fn constant(value: &Constant) -> String {
+    match value {
+        Constant::Identifier(name) => name.clone(),
+        Constant::Integer(value) => value.to_string(),
+        Constant::UnsignedInteger(value) => value.to_string(),
+        Constant::Float(value) => float(*value),
+        Constant::String(value) => quote(value),
+        Constant::Bytes(value) => quote_bytes(value),
+        Constant::Bool(value) => value.to_string(),
+        Constant::Aggregate(fields) if fields.is_empty() => "{}".to_string(),
+        Constant::Aggregate(fields) => {
+            let fields: Vec<_> = fields
+                .iter()
+                .map(|field| format!("{}: {}", field.name, constant(&field.value)))
+                .collect();
+            format!("{{ {} }}", fields.join(" "))
+        }
+        Constant::List(values) => {
+            let values: Vec<_> = values.iter().map(constant).collect();
+            format!("[{}]", values.join(", "))
+        }
+    }
+}
+
+/// Formats a float so that it reads back as the same float, never as an integer.
+fn float(value: f64) -> String {
+    if value.is_nan() {
+        "nan".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "inf" } else { "-inf" }.to_string()
+    } else {
+        format!("{:?}", value)
+    }
+}
+
+/// Formats a string literal in double quotes, escaping quotes, backslashes and control characters.
+fn quote(value: &str) -> String {
+    quote_bytes(value.as_bytes())
+}
+
+/// Formats a string literal in double quotes like [`quote`], writing bytes that are not valid
+/// UTF-8 as octal escapes.
+fn quote_bytes(value: &[u8]) -> String {
+    let mut literal = String::from("\"");
+    for chunk in value.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '"' => literal.push_str("\\\""),
+                '\\' => literal.push_str("\\\\"),
+                '\n' => literal.push_str("\\n"),
+                '\r' => literal.push_str("\\r"),
+                '\t' => literal.push_str("\\t"),
+                c if c.is_ascii_control() => {
+                    let _ = write!(literal, "\\{:03o}", c as u32);
+                }
+                c => literal.push(c),
+            }
+        }
+        for byte in chunk.invalid() {
+            let _ = write!(literal, "\\{:03o}", byte);
+        }
+    }
+    literal.push('"');
+    literal
+}
@@ -0,0 +1,188 @@
+//! Source locations of parsed definitions.
+
+use crate::{Comments, EnumDef, Extend, Field, Message, Oneof, OptionDef, Proto, Rule, Service};
+use serde::{Deserialize, Serialize};
+
+/// Represents the location of a definition in its source file.
+///
+/// Offsets are in bytes and lines and columns start at 1; the end of a span points just
+/// past its last character. Definitions that were not parsed from source have an unknown
+/// span, in which every position is zero.
+///
+/// Spans take part in comparisons of definitions; use [`Proto::eq_ignoring_spans`] to compare
+/// the same definition parsed from different places, built in code or loaded from JSON.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Span {
+    /// Identifier of the source file, as given to [`Proto::parse_with_file_id`]
+    pub file_id: usize,
+    /// Byte offset of the first character
+    pub start: usize,
+    /// Byte offset just past the last character
+    pub end: usize,
+    /// Line of the first character
+    pub start_line: usize,
+    /// Column of the first character
+    pub start_column: usize,
+    /// Line of the position just past the last character
+    pub end_line: usize,
+    /// Column of the position just past the last character
+    pub end_column: usize,
+}
+
+impl Span {
+    /// Creates the span covered by a pest Pair.
+    pub(crate) fn from_pair(pair: &pest::iterators::Pair<Rule>) -> Self {
+        let (start_line, start_column) = pair.line_col();
+        let (mut end_line, mut end_column) = (start_line, start_column);
+        for c in pair.as_str().chars() {
+            if c == '\n' {
+                end_line += 1;
+                end_column = 1;
+            } else {
+                end_column += 1;
+            }
+        }
+
+        Span {
+            file_id: 0,
+            start: pair.as_span().start(),
+            end: pair.as_span().end(),
+            start_line,
+            start_column,
+            end_line,
+            end_column,
+        }
+    }
+
+    /// Indicates if the location is unknown, e.g. for definitions built in code.
+    pub fn is_unknown(&self) -> bool {
+        self.start_line == 0
+    }
+}
+
+impl Proto {
+    /// Returns a copy of the proto definition in which every span is unknown.
+    pub fn without_spans(&self) -> Proto {
+        let mut proto = self.clone();
+        proto.for_each_location(&mut |span, _| *span = Span::default());
+        proto
+    }
+
+    /// Compares two proto definitions, ignoring the spans of the file and its definitions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use proto_file_parser::Proto;
+    ///
+    /// let compact = Proto::parse("syntax = 'proto3'; message User {}").unwrap();
+    /// let spread = Proto::parse("syntax = 'proto3';\n\nmessage User {\n}\n").unwrap();
+    /// assert_ne!(compact, spread);
+    /// assert!(compact.eq_ignoring_spans(&spread));
+    /// ```
+    pub fn eq_ignoring_spans(&self, other: &Proto) -> bool {
+        self.without_spans() == other.without_spans()
+    }
+
+    /// Calls `f` with the span of the file and of every definition in it,
+    /// together with the comments of definitions that can have comments attached.
+    pub(crate) fn for_each_location(
+        &mut self,
+        f: &mut impl FnMut(&mut Span, Option<&mut Comments>),
+    ) {
+        f(&mut self.span, None);
+        for import in &mut self.imports {
+            f(&mut import.span, Some(&mut import.comments));
+        }
+        for message in &mut self.messages {
+            message_locations(message, f);
+        }
+        for enum_def in &mut self.enums {
+            enum_locations(enum_def, f);
+        }
+        for service in &mut self.services {
+            service_locations(service, f);
+        }
+        for extend in &mut self.extends {
+            extend_locations(extend, f);
+        }
+        option_locations(&mut self.options, f);
+    }
+}
+
+fn message_locations(message: &mut Message, f: &mut impl FnMut(&mut Span, Option<&mut Comments>)) {
+    f(&mut message.span, Some(&mut message.comments));
+    for field in &mut message.fields {
+        field_locations(field, f);
+    }
+    for oneof in &mut message.oneofs {
+        oneof_locations(oneof, f);
+    }
+    for nested in &mut message.nested_messages {
+        message_locations(nested, f);
+    }
+    for nested in &mut message.nested_enums {
+        enum_locations(nested, f);
+    }
+    for extend in &mut message.extends {
+        extend_locations(extend, f);
+    }
+    for range in &mut message.extension_ranges {
+        f(&mut range.span, None);
+        option_locations(&mut range.options, f);
+    }
+    for range in &mut message.reserved_ranges {
+        f(&mut range.span, None);
+    }
+    option_locations(&mut message.options, f);
+}
+
+fn field_locations(field: &mut Field, f: &mut impl FnMut(&mut Span, Option<&mut Comments>)) {
+    f(&mut field.span, Some(&mut field.comments));
+    option_locations(&mut field.options, f);
+}
+
+fn oneof_locations(oneof: &mut Oneof, f: &mut impl FnMut(&mut Span, Option<&mut Comments>)) {
+    f(&mut oneof.span, Some(&mut oneof.comments));
+    for field in &mut oneof.fields {
+        field_locations(field, f);
+    }
+    option_locations(&mut oneof.options, f);
+}
+
+fn enum_locations(enum_def: &mut EnumDef, f: &mut impl FnMut(&mut Span, Option<&mut Comments>)) {
+    f(&mut enum_def.span, Some(&mut enum_def.comments));
+    for value in &mut enum_def.values {
+        f(&mut value.span, Some(&mut value.comments));
+        option_locations(&mut value.options, f);
+    }
+    for range in &mut enum_def.reserved_ranges {
+        f(&mut range.span, None);
+    }
+    option_locations(&mut enum_def.options, f);
+}
+
+fn service_locations(service: &mut Service, f: &mut impl FnMut(&mut Span, Option<&mut Comments>)) {
+    f(&mut service.span, Some(&mut service.comments));
+    for method in &mut service.methods {
+        f(&mut method.span, Some(&mut method.comments));
+        option_locations(&mut method.options, f);
+    }
+    option_locations(&mut service.options, f);
+}
+
+fn extend_locations(extend: &mut Extend, f: &mut impl FnMut(&mut Span, Option<&mut Comments>)) {
+    f(&mut extend.span, Some(&mut extend.comments));
+    for field in &mut extend.fields {
+        field_locations(field, f);
+    }
+}
+
+fn option_locations(
+    options: &mut [OptionDef],
+    f: &mut impl FnMut(&mut Span, Option<&mut Comments>),
+) {
+    for option in options {
+        f(&mut option.span, Some(&mut option.comments));
+    }
+}
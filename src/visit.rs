@@ -0,0 +1,270 @@
+//! Traversal of the definitions of a proto file.
+//!
+//! [`Visit`] walks a [`Proto`] by reference and [`VisitMut`] by mutable reference. Every method
+//! has a default implementation that visits the children of its definition by calling the
+//! matching `walk_*` function, so an implementation only overrides the callbacks it needs and
+//! calls the `walk_*` function itself to keep descending.
+//!
+//! Each callback receives the fully-qualified scope of its definition, using the same names as
+//! [`Proto::resolve_features`]:
+//!
+//! * messages, enums, services and extend blocks get the package or message they are
+//!   declared in (e.g. `acme.v1` or `acme.v1.User`)
+//! * fields get the message they belong to, including fields of oneofs, while extension
+//!   fields get the scope of their `extend` block
+//! * oneofs get the message they belong to
+//! * enum values get their enum (e.g. `acme.v1.Status`)
+//! * methods get their service (e.g. `acme.v1.Users`)
+//!
+//! Children are visited grouped by kind: fields, oneofs, nested messages, nested enums and
+//! extend blocks of a message, and messages, enums, extend blocks and services of a file.
+//!
+//! # Examples
+//!
+//! ```
+//! use proto_file_parser::{Field, Proto, Visit};
+//!
+//! struct FieldNames(Vec<String>);
+//!
+//! impl Visit for FieldNames {
+//!     fn visit_field(&mut self, field: &Field, scope: &str) {
+//!         self.0.push(format!("{}.{}", scope, field.name));
+//!     }
+//! }
+//!
+//! let proto = Proto::parse(r#"
+//!     syntax = "proto3";
+//!     package acme;
+//!     message User {
+//!         string name = 1;
+//!         message Address { string city = 1; }
+//!     }
+//! "#).unwrap();
+//!
+//! let mut names = FieldNames(Vec::new());
+//! names.visit_proto(&proto);
+//! assert_eq!(names.0, ["acme.User.name", "acme.User.Address.city"]);
+//! ```
+
+use crate::{EnumDef, EnumValue, Extend, Field, Message, Method, Oneof, Proto, Service};
+
+/// Visits the definitions of a proto file by reference.
+pub trait Visit {
+    /// Visits a proto file.
+    fn visit_proto(&mut self, proto: &Proto) {
+        walk_proto(self, proto);
+    }
+
+    /// Visits a message declared in `scope`.
+    fn visit_message(&mut self, message: &Message, scope: &str) {
+        walk_message(self, message, scope);
+    }
+
+    /// Visits a field of the message `scope`, or an extension field declared in `scope`.
+    fn visit_field(&mut self, _field: &Field, _scope: &str) {}
+
+    /// Visits a oneof of the message `scope`.
+    fn visit_oneof(&mut self, oneof: &Oneof, scope: &str) {
+        walk_oneof(self, oneof, scope);
+    }
+
+    /// Visits an enum declared in `scope`.
+    fn visit_enum(&mut self, enum_def: &EnumDef, scope: &str) {
+        walk_enum(self, enum_def, scope);
+    }
+
+    /// Visits a value of the enum `scope`.
+    fn visit_enum_value(&mut self, _value: &EnumValue, _scope: &str) {}
+
+    /// Visits a service declared in `scope`.
+    fn visit_service(&mut self, service: &Service, scope: &str) {
+        walk_service(self, service, scope);
+    }
+
+    /// Visits a method of the service `scope`.
+    fn visit_method(&mut self, _method: &Method, _scope: &str) {}
+
+    /// Visits an extend block declared in `scope`.
+    fn visit_extend(&mut self, extend: &Extend, scope: &str) {
+        walk_extend(self, extend, scope);
+    }
+}
+
+/// Visits the top-level definitions of a proto file.
+pub fn walk_proto<V: Visit + ?Sized>(visitor: &mut V, proto: &Proto) {
+    let scope = proto.package.as_deref().unwrap_or_default();
+    for message in &proto.messages {
+        visitor.visit_message(message, scope);
+    }
+    for enum_def in &proto.enums {
+        visitor.visit_enum(enum_def, scope);
+    }
+    for extend in &proto.extends {
+        visitor.visit_extend(extend, scope);
+    }
+    for service in &proto.services {
+        visitor.visit_service(service, scope);
+    }
+}
+
+/// Visits the fields, oneofs and nested definitions of a message declared in `scope`.
+pub fn walk_message<V: Visit + ?Sized>(visitor: &mut V, message: &Message, scope: &str) {
+    let name = Proto::qualify(scope, &message.name);
+    for field in &message.fields {
+        visitor.visit_field(field, &name);
+    }
+    for oneof in &message.oneofs {
+        visitor.visit_oneof(oneof, &name);
+    }
+    for nested in &message.nested_messages {
+        visitor.visit_message(nested, &name);
+    }
+    for nested in &message.nested_enums {
+        visitor.visit_enum(nested, &name);
+    }
+    for extend in &message.extends {
+        visitor.visit_extend(extend, &name);
+    }
+}
+
+/// Visits the fields of a oneof of the message `scope`.
+pub fn walk_oneof<V: Visit + ?Sized>(visitor: &mut V, oneof: &Oneof, scope: &str) {
+    for field in &oneof.fields {
+        visitor.visit_field(field, scope);
+    }
+}
+
+/// Visits the values of an enum declared in `scope`.
+pub fn walk_enum<V: Visit + ?Sized>(visitor: &mut V, enum_def: &EnumDef, scope: &str) {
+    let name = Proto::qualify(scope, &enum_def.name);
+    for value in &enum_def.values {
+        visitor.visit_enum_value(value, &name);
+    }
+}
+
+/// Visits the methods of a service declared in `scope`.
+pub fn walk_service<V: Visit + ?Sized>(visitor: &mut V, service: &Service, scope: &str) {
+    let name = Proto::qualify(scope, &service.name);
+    for method in &service.methods {
+        visitor.visit_method(method, &name);
+    }
+}
+
+/// Visits the fields of an extend block declared in `scope`.
+pub fn walk_extend<V: Visit + ?Sized>(visitor: &mut V, extend: &Extend, scope: &str) {
+    for field in &extend.fields {
+        visitor.visit_field(field, scope);
+    }
+}
+
+/// Visits the definitions of a proto file by mutable reference.
+///
+/// Scopes are computed from the names of the enclosing definitions when their children are
+/// walked, so renaming a message before walking it changes the scope of its children.
+pub trait VisitMut {
+    /// Visits a proto file.
+    fn visit_proto_mut(&mut self, proto: &mut Proto) {
+        walk_proto_mut(self, proto);
+    }
+
+    /// Visits a message declared in `scope`.
+    fn visit_message_mut(&mut self, message: &mut Message, scope: &str) {
+        walk_message_mut(self, message, scope);
+    }
+
+    /// Visits a field of the message `scope`, or an extension field declared in `scope`.
+    fn visit_field_mut(&mut self, _field: &mut Field, _scope: &str) {}
+
+    /// Visits a oneof of the message `scope`.
+    fn visit_oneof_mut(&mut self, oneof: &mut Oneof, scope: &str) {
+        walk_oneof_mut(self, oneof, scope);
+    }
+
+    /// Visits an enum declared in `scope`.
+    fn visit_enum_mut(&mut self, enum_def: &mut EnumDef, scope: &str) {
+        walk_enum_mut(self, enum_def, scope);
+    }
+
+    /// Visits a value of the enum `scope`.
+    fn visit_enum_value_mut(&mut self, _value: &mut EnumValue, _scope: &str) {}
+
+    /// Visits a service declared in `scope`.
+    fn visit_service_mut(&mut self, service: &mut Service, scope: &str) {
+        walk_service_mut(self, service, scope);
+    }
+
+    /// Visits a method of the service `scope`.
+    fn visit_method_mut(&mut self, _method: &mut Method, _scope: &str) {}
+
+    /// Visits an extend block declared in `scope`.
+    fn visit_extend_mut(&mut self, extend: &mut Extend, scope: &str) {
+        walk_extend_mut(self, extend, scope);
+    }
+}
+
+/// Visits the top-level definitions of a proto file.
+pub fn walk_proto_mut<V: VisitMut + ?Sized>(visitor: &mut V, proto: &mut Proto) {
+    let scope = proto.package.clone().unwrap_or_default();
+    for message in &mut proto.messages {
+        visitor.visit_message_mut(message, &scope);
+    }
+    for enum_def in &mut proto.enums {
+        visitor.visit_enum_mut(enum_def, &scope);
+    }
+    for extend in &mut proto.extends {
+        visitor.visit_extend_mut(extend, &scope);
+    }
+    for service in &mut proto.services {
+        visitor.visit_service_mut(service, &scope);
+    }
+}
+
+/// Visits the fields, oneofs and nested definitions of a message declared in `scope`.
+pub fn walk_message_mut<V: VisitMut + ?Sized>(visitor: &mut V, message: &mut Message, scope: &str) {
+    let name = Proto::qualify(scope, &message.name);
+    for field in &mut message.fields {
+        visitor.visit_field_mut(field, &name);
+    }
+    for oneof in &mut message.oneofs {
+        visitor.visit_oneof_mut(oneof, &name);
+    }
+    for nested in &mut message.nested_messages {
+        visitor.visit_message_mut(nested, &name);
+    }
+    for nested in &mut message.nested_enums {
+        visitor.visit_enum_mut(nested, &name);
+    }
+    for extend in &mut message.extends {
+        visitor.visit_extend_mut(extend, &name);
+    }
+}
+
+/// Visits the fields of a oneof of the message `scope`.
+pub fn walk_oneof_mut<V: VisitMut + ?Sized>(visitor: &mut V, oneof: &mut Oneof, scope: &str) {
+    for field in &mut oneof.fields {
+        visitor.visit_field_mut(field, scope);
+    }
+}
+
+/// Visits the values of an enum declared in `scope`.
+pub fn walk_enum_mut<V: VisitMut + ?Sized>(visitor: &mut V, enum_def: &mut EnumDef, scope: &str) {
+    let name = Proto::qualify(scope, &enum_def.name);
+    for value in &mut enum_def.values {
+        visitor.visit_enum_value_mut(value, &name);
+    }
+}
+
+/// Visits the methods of a service declared in `scope`.
+pub fn walk_service_mut<V: VisitMut + ?Sized>(visitor: &mut V, service: &mut Service, scope: &str) {
+    let name = Proto::qualify(scope, &service.name);
+    for method in &mut service.methods {
+        visitor.visit_method_mut(method, &name);
+    }
+}
+
+/// Visits the fields of an extend block declared in `scope`.
+pub fn walk_extend_mut<V: VisitMut + ?Sized>(visitor: &mut V, extend: &mut Extend, scope: &str) {
+    for field in &mut extend.fields {
+        visitor.visit_field_mut(field, scope);
+    }
+}
@@ -5,7 +5,7 @@ mod tests {
     #[test]
     fn test_whitespace_rule() -> Result<(), ParserError> {
         let input = "syntax    =     \"proto3\";\n\t\rmessage Test {\n    string name = 1;\r\n}";
-        let json = Proto::parse(input)?;
+        let json = Proto::parse(input)?.to_json()?;
         let value: serde_json::Value = serde_json::from_str(&json).unwrap();
         assert_eq!(value["syntax"], "proto3");
         assert_eq!(value["messages"][0]["name"], "Test");
@@ -26,7 +26,7 @@ mod tests {
                               field comment */
         }
     "#;
-        let json = Proto::parse(input)?;
+        let json = Proto::parse(input)?.to_json()?;
         let value: serde_json::Value = serde_json::from_str(&json).unwrap();
         assert_eq!(value["syntax"], "proto3");
         assert_eq!(value["messages"][0]["name"], "Test");
@@ -50,7 +50,7 @@ mod tests {
         string mixtureOfALLStyles123 = 8;
     }
 "#;
-        let json = Proto::parse(input)?;
+        let json = Proto::parse(input)?.to_json()?;
         let value: serde_json::Value = serde_json::from_str(&json).unwrap();
         let fields = &value["messages"][0]["fields"];
         assert_eq!(fields[0]["name"], "normal");
@@ -79,7 +79,7 @@ mod tests {
             string field7 = 536870911; // Max allowed
         }
     "#;
-        let json = Proto::parse(input)?;
+        let json = Proto::parse(input)?.to_json()?;
         let value: serde_json::Value = serde_json::from_str(&json).unwrap();
         let fields = &value["messages"][0]["fields"];
         assert_eq!(fields[0]["tag"], 1);
@@ -106,17 +106,17 @@ mod tests {
         import "MixedCase.proto";
         import "with_numbers123.proto";
     "#;
-        let json = Proto::parse(input)?;
+        let json = Proto::parse(input)?.to_json()?;
         let value: serde_json::Value = serde_json::from_str(&json).unwrap();
         let imports = value["imports"].as_array().unwrap();
-        assert_eq!(imports[0], "simple/path.proto");
-        assert_eq!(imports[1], "path/with/multiple/segments.proto");
-        assert_eq!(imports[2], "path_with_underscore.proto");
-        assert_eq!(imports[3], "path.with.dots.proto");
-        assert_eq!(imports[4], "path-with-dashes.proto");
-        assert_eq!(imports[5], "UPPERCASE.proto");
-        assert_eq!(imports[6], "MixedCase.proto");
-        assert_eq!(imports[7], "with_numbers123.proto");
+        assert_eq!(imports[0]["path"], "simple/path.proto");
+        assert_eq!(imports[1]["path"], "path/with/multiple/segments.proto");
+        assert_eq!(imports[2]["path"], "path_with_underscore.proto");
+        assert_eq!(imports[3]["path"], "path.with.dots.proto");
+        assert_eq!(imports[4]["path"], "path-with-dashes.proto");
+        assert_eq!(imports[5]["path"], "UPPERCASE.proto");
+        assert_eq!(imports[6]["path"], "MixedCase.proto");
+        assert_eq!(imports[7]["path"], "with_numbers123.proto");
         Ok(())
     }
 
@@ -127,7 +127,7 @@ mod tests {
         syntax = "proto3";
         package com.example.project.v1;
     "#;
-        let json = Proto::parse(input)?;
+        let json = Proto::parse(input)?.to_json()?;
         let value: serde_json::Value = serde_json::from_str(&json).unwrap();
         assert_eq!(value["package"], "com.example.project.v1");
         Ok(())
@@ -137,7 +137,7 @@ mod tests {
     #[test]
     fn test_syntax_rule() -> Result<(), ParserError> {
         let input = r#"syntax = "proto3";"#;
-        let json = Proto::parse(input)?;
+        let json = Proto::parse(input)?.to_json()?;
         let value: serde_json::Value = serde_json::from_str(&json).unwrap();
         assert_eq!(value["syntax"], "proto3");
         Ok(())
@@ -150,7 +150,7 @@ mod tests {
         syntax = "proto3";
         package test.v1.api;
     "#;
-        let json = Proto::parse(input)?;
+        let json = Proto::parse(input)?.to_json()?;
         let value: serde_json::Value = serde_json::from_str(&json).unwrap();
         assert_eq!(value["package"], "test.v1.api");
         Ok(())
@@ -163,10 +163,10 @@ mod tests {
         syntax = "proto3";
         import "other.proto";
     "#;
-        let json = Proto::parse(input)?;
+        let json = Proto::parse(input)?.to_json()?;
         let value: serde_json::Value = serde_json::from_str(&json).unwrap();
         let imports = value["imports"].as_array().unwrap();
-        assert_eq!(imports[0], "other.proto");
+        assert_eq!(imports[0]["path"], "other.proto");
         Ok(())
     }
 
@@ -182,12 +182,13 @@ mod tests {
             required string required_field = 4;
         }
     "#;
-        let json = Proto::parse(input)?;
+        let json = Proto::parse(input)?.to_json()?;
         let value: serde_json::Value = serde_json::from_str(&json).unwrap();
         let fields = &value["messages"][0]["fields"];
-        assert_eq!(fields[0]["repeated"], false);
-        assert_eq!(fields[1]["repeated"], true);
-        // Note: optional and required are handled according to your implementation
+        assert_eq!(fields[0]["label"], "implicit");
+        assert_eq!(fields[1]["label"], "repeated");
+        assert_eq!(fields[2]["label"], "optional");
+        assert_eq!(fields[3]["label"], "required");
         Ok(())
     }
 
@@ -214,7 +215,7 @@ mod tests {
             bytes bytes_field = 15;
         }
     "#;
-        let json = Proto::parse(input)?;
+        let json = Proto::parse(input)?.to_json()?;
         let value: serde_json::Value = serde_json::from_str(&json).unwrap();
         let fields = &value["messages"][0]["fields"];
         let types = [
@@ -238,7 +239,7 @@ mod tests {
             rpc Method2 (stream StreamingRequest) returns (stream StreamingResponse);
         }
     "#;
-        let json = Proto::parse(input)?;
+        let json = Proto::parse(input)?.to_json()?;
         let value: serde_json::Value = serde_json::from_str(&json).unwrap();
         let methods = &value["services"][0]["methods"];
         assert_eq!(methods[0]["input_type"], "Request");
@@ -276,7 +277,7 @@ mod tests {
             ;
         }
     "#;
-        let json = Proto::parse(input)?;
+        let json = Proto::parse(input)?.to_json()?;
         let value: serde_json::Value = serde_json::from_str(&json).unwrap();
         let message = &value["messages"][0];
 
@@ -311,7 +312,7 @@ mod tests {
             DELETED = 4;
         }
     "#;
-        let json = Proto::parse(input)?;
+        let json = Proto::parse(input)?.to_json()?;
         let value: serde_json::Value = serde_json::from_str(&json).unwrap();
 
         let enum_def = &value["enums"][0];
@@ -345,7 +346,7 @@ mod tests {
         LARGE_NUMBER = 100;
     }
 "#;
-        let json = Proto::parse(input)?;
+        let json = Proto::parse(input)?.to_json()?;
         let value: serde_json::Value = serde_json::from_str(&json).unwrap();
 
         let values = &value["enums"][0]["values"];
@@ -381,7 +382,7 @@ mod tests {
             rpc Method3 (stream Request3) returns (stream Response3);
         }
     "#;
-        let json = Proto::parse(input)?;
+        let json = Proto::parse(input)?.to_json()?;
         let value: serde_json::Value = serde_json::from_str(&json).unwrap();
 
         let services = value["services"].as_array().unwrap();
@@ -434,7 +435,7 @@ mod tests {
         rpc BidiStream (stream StreamRequest) returns (stream StreamResponse);
     }
 "#;
-        let json = Proto::parse(input)?;
+        let json = Proto::parse(input)?.to_json()?;
         let value: serde_json::Value = serde_json::from_str(&json).unwrap();
 
         let methods = &value["services"][0]["methods"];
@@ -465,6 +466,47 @@ mod tests {
         Ok(())
     }
 
+    // Testing rpc_element rule and empty statements
+    #[test]
+    fn test_rpc_element_rule() -> Result<(), ParserError> {
+        let input = r#"
+    syntax = "proto3";;
+    import "google/api/annotations.proto";
+    ;
+    service Api {
+        ;
+        rpc Get (GetRequest) returns (GetResponse) {
+            option (google.api.http) = { get: "/v1/{name=items/*}" };
+            ;
+            option deprecated = true;
+        }
+        rpc Empty (EmptyRequest) returns (EmptyResponse) { ; };
+    };
+    enum Status {
+        ;
+        STATUS_UNKNOWN = 0;;
+    }
+    message Item {};
+"#;
+        let json = Proto::parse(input)?.to_json()?;
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        let methods = &value["services"][0]["methods"];
+        assert_eq!(methods.as_array().unwrap().len(), 2);
+        assert_eq!(methods[0]["options"][0]["name"], "(google.api.http)");
+        assert_eq!(
+            methods[0]["options"][0]["value"]["aggregate"][0]["value"]["string"],
+            "/v1/{name=items/*}"
+        );
+        assert_eq!(methods[0]["options"][1]["name"], "deprecated");
+        assert!(methods[1]["options"].as_array().unwrap().is_empty());
+
+        assert_eq!(value["enums"][0]["values"][0]["name"], "STATUS_UNKNOWN");
+        assert_eq!(value["messages"][0]["name"], "Item");
+
+        Ok(())
+    }
+
     // Testing field rule
     #[test]
     fn test_field_definition_rule() -> Result<(), ParserError> {
@@ -506,7 +548,7 @@ mod tests {
         sfixed64 sfixed64_field = 24;
     }
 "#;
-        let json = Proto::parse(input)?;
+        let json = Proto::parse(input)?.to_json()?;
         let value: serde_json::Value = serde_json::from_str(&json).unwrap();
 
         let fields = &value["messages"][0]["fields"];
@@ -515,17 +557,17 @@ mod tests {
         assert_eq!(fields[0]["name"], "simple_field");
         assert_eq!(fields[0]["type_name"], "string");
         assert_eq!(fields[0]["tag"], 1);
-        assert_eq!(fields[0]["repeated"], false);
+        assert_eq!(fields[0]["label"], "implicit");
 
         // Test repeated fields
         assert_eq!(fields[2]["name"], "repeated_field");
         assert_eq!(fields[2]["type_name"], "string");
-        assert_eq!(fields[2]["repeated"], true);
+        assert_eq!(fields[2]["label"], "repeated");
 
         // Test message type fields
         assert_eq!(fields[6]["name"], "repeated_message");
         assert_eq!(fields[6]["type_name"], "OtherMessage");
-        assert_eq!(fields[6]["repeated"], true);
+        assert_eq!(fields[6]["label"], "repeated");
 
         // Test enum type fields
         assert_eq!(fields[7]["name"], "enum_field");
@@ -590,7 +632,7 @@ mod tests {
             rpc Get (Test) returns (Test);
         }
     "#;
-        let json = Proto::parse(input)?;
+        let json = Proto::parse(input)?.to_json()?;
         let value: serde_json::Value = serde_json::from_str(&json).unwrap();
 
         // Verify syntax
@@ -601,8 +643,8 @@ mod tests {
 
         // Verify imports
         let imports = value["imports"].as_array().unwrap();
-        assert_eq!(imports[0], "other.proto");
-        assert_eq!(imports[1], "another.proto");
+        assert_eq!(imports[0]["path"], "other.proto");
+        assert_eq!(imports[1]["path"], "another.proto");
 
         // Verify top-level enum
         let enums = value["enums"].as_array().unwrap();
@@ -630,4 +672,1960 @@ mod tests {
 
         Ok(())
     }
-}
\ No newline at end of file
+    // Testing oneof_def rule
+    #[test]
+    fn test_oneof_def_rule() -> Result<(), ParserError> {
+        let input = r#"
+        syntax = "proto3";
+        message SampleMessage {
+            string id = 1;
+            oneof test_oneof {
+                option deprecated = true;
+                string name = 4;
+                SubMessage sub_message = 9;
+                ;
+            }
+        }
+    "#;
+        let json = Proto::parse(input)?.to_json()?;
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let message = &value["messages"][0];
+
+        // Regular fields stay outside the oneof
+        assert_eq!(message["fields"].as_array().unwrap().len(), 1);
+        assert_eq!(message["fields"][0]["name"], "id");
+
+        let oneof = &message["oneofs"][0];
+        assert_eq!(oneof["name"], "test_oneof");
+        assert_eq!(oneof["fields"][0]["name"], "name");
+        assert_eq!(oneof["fields"][0]["type_name"], "string");
+        assert_eq!(oneof["fields"][0]["tag"], 4);
+        assert_eq!(oneof["fields"][1]["name"], "sub_message");
+        assert_eq!(oneof["fields"][1]["type_name"], "SubMessage");
+        assert_eq!(oneof["fields"][1]["tag"], 9);
+        assert_eq!(oneof["options"][0]["name"], "deprecated");
+        assert_eq!(oneof["options"][0]["value"]["bool"], true);
+
+        Ok(())
+    }
+
+    // Testing map_field rule
+    #[test]
+    fn test_map_field_rule() -> Result<(), ParserError> {
+        let input = r#"
+        syntax = "proto3";
+        message MapTest {
+            map<string, int64> counts = 1;
+            map<int32, Project> projects = 2;
+            string name = 3;
+        }
+    "#;
+        let json = Proto::parse(input)?.to_json()?;
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let fields = &value["messages"][0]["fields"];
+
+        assert_eq!(fields[0]["name"], "counts");
+        assert_eq!(fields[0]["tag"], 1);
+        assert_eq!(fields[0]["type_name"], "map<string, int64>");
+        assert_eq!(fields[0]["kind"]["map"]["key_type"], "string");
+        assert_eq!(fields[0]["kind"]["map"]["value_type"], "int64");
+        assert_eq!(fields[0]["label"], "repeated");
+
+        assert_eq!(fields[1]["name"], "projects");
+        assert_eq!(fields[1]["kind"]["map"]["key_type"], "int32");
+        assert_eq!(fields[1]["kind"]["map"]["value_type"], "Project");
+
+        assert_eq!(fields[2]["kind"], "normal");
+
+        Ok(())
+    }
+
+    // Testing map key type validation
+    #[test]
+    fn test_map_field_invalid_key_type() {
+        for key_type in ["float", "double", "bytes", "Project"] {
+            let input = format!(
+                "syntax = \"proto3\"; message MapTest {{ map<{}, string> values = 1; }}",
+                key_type
+            );
+            assert!(
+                matches!(Proto::parse(&input), Err(ParserError::SyntaxError(_))),
+                "map key type {} should be rejected",
+                key_type
+            );
+        }
+    }
+
+    // Testing option_stmt rule
+    #[test]
+    fn test_option_stmt_rule() -> Result<(), ParserError> {
+        let input = r#"
+        syntax = "proto3";
+        package acme.v1;
+        option go_package = "github.com/acme/api/v1";
+        option java_multiple_files = true;
+        option optimize_for = SPEED;
+
+        message Request {
+            option (my.ext).sub = 42;
+            option (acme.rules) = { min: 1 max: 10 tags: ["a", "b"] nested { flag: true } };
+            string name = 1 [deprecated = true, (acme.field).max_len = 64];
+            map<string, int32> counts = 2 [(acme.map) = "x"];
+        }
+
+        enum Status {
+            option allow_alias = true;
+            UNKNOWN = 0;
+            STARTED = 1 [(acme.label) = "started"];
+        }
+
+        service Api {
+            option (acme.service) = "api";
+            rpc Get (Request) returns (Request) {
+                option idempotency_level = NO_SIDE_EFFECTS;
+            }
+        }
+    "#;
+        let json = Proto::parse(input)?.to_json()?;
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        // File-level options
+        let options = &value["options"];
+        assert_eq!(options[0]["name"], "go_package");
+        assert_eq!(options[0]["value"]["string"], "github.com/acme/api/v1");
+        assert_eq!(options[1]["name"], "java_multiple_files");
+        assert_eq!(options[1]["value"]["bool"], true);
+        assert_eq!(options[2]["name"], "optimize_for");
+        assert_eq!(options[2]["value"]["identifier"], "SPEED");
+
+        // Message-level options with custom names and aggregate values
+        let message = &value["messages"][0];
+        assert_eq!(message["options"][0]["name"], "(my.ext).sub");
+        assert_eq!(message["options"][0]["value"]["integer"], 42);
+        assert_eq!(message["options"][1]["name"], "(acme.rules)");
+        let aggregate = &message["options"][1]["value"]["aggregate"];
+        assert_eq!(aggregate[0]["name"], "min");
+        assert_eq!(aggregate[0]["value"]["integer"], 1);
+        assert_eq!(aggregate[1]["name"], "max");
+        assert_eq!(aggregate[1]["value"]["integer"], 10);
+        assert_eq!(aggregate[2]["name"], "tags");
+        assert_eq!(aggregate[2]["value"]["list"][1]["string"], "b");
+        assert_eq!(aggregate[3]["name"], "nested");
+        assert_eq!(aggregate[3]["value"]["aggregate"][0]["name"], "flag");
+        assert_eq!(aggregate[3]["value"]["aggregate"][0]["value"]["bool"], true);
+
+        // Field-level options
+        let fields = &message["fields"];
+        assert_eq!(fields[0]["deprecated"], true);
+        assert_eq!(fields[0]["options"][0]["name"], "(acme.field).max_len");
+        assert_eq!(fields[0]["options"][0]["value"]["integer"], 64);
+        assert_eq!(fields[1]["options"][0]["name"], "(acme.map)");
+
+        // Enum and enum value options
+        let enum_def = &value["enums"][0];
+        assert_eq!(enum_def["options"][0]["name"], "allow_alias");
+        assert_eq!(enum_def["values"][1]["options"][0]["name"], "(acme.label)");
+        assert_eq!(enum_def["values"][1]["options"][0]["value"]["string"], "started");
+
+        // Service and method options
+        let service = &value["services"][0];
+        assert_eq!(service["options"][0]["name"], "(acme.service)");
+        assert_eq!(service["methods"][0]["options"][0]["name"], "idempotency_level");
+        assert_eq!(
+            service["methods"][0]["options"][0]["value"]["identifier"],
+            "NO_SIDE_EFFECTS"
+        );
+
+        Ok(())
+    }
+
+    // Testing field_options rule
+    #[test]
+    fn test_field_options_rule() -> Result<(), ParserError> {
+        let input = r#"
+        syntax = "proto2";
+        message Options {
+            optional string name = 1 [default = "none", json_name = "displayName"];
+            repeated int32 ids = 2 [packed = true, deprecated = true];
+            optional Child child = 3 [lazy = true, debug_redact = false, (acme.note) = "x"];
+            optional int32 plain = 4;
+        }
+        enum Kind {
+            KIND_UNKNOWN = 0;
+            KIND_OLD = 1 [deprecated = true, debug_redact = true, (acme.label) = "old"];
+        }
+    "#;
+        let json = Proto::parse(input)?.to_json()?;
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let fields = &value["messages"][0]["fields"];
+
+        assert_eq!(fields[0]["default"]["string"], "none");
+        assert_eq!(fields[0]["json_name"], "displayName");
+        assert!(fields[0]["options"].as_array().unwrap().is_empty());
+
+        assert_eq!(fields[1]["packed"], true);
+        assert_eq!(fields[1]["deprecated"], true);
+
+        assert_eq!(fields[2]["lazy"], true);
+        assert_eq!(fields[2]["debug_redact"], false);
+        assert_eq!(fields[2]["options"][0]["name"], "(acme.note)");
+
+        // Unset well-known options stay empty
+        assert!(fields[3]["deprecated"].is_null());
+        assert!(fields[3]["packed"].is_null());
+        assert!(fields[3]["json_name"].is_null());
+        assert!(fields[3]["default"].is_null());
+
+        let values = &value["enums"][0]["values"];
+        assert!(values[0]["deprecated"].is_null());
+        assert_eq!(values[1]["deprecated"], true);
+        assert_eq!(values[1]["debug_redact"], true);
+        assert_eq!(values[1]["options"][0]["name"], "(acme.label)");
+
+        Ok(())
+    }
+
+    // Testing well-known field option validation
+    #[test]
+    fn test_field_options_invalid() {
+        let inputs = [
+            r#"message M { string a = 1 [deprecated = "yes"]; }"#,
+            r#"message M { string a = 1 [json_name = 1]; }"#,
+            r#"message M { string a = 1 [deprecated = true, deprecated = false]; }"#,
+        ];
+        for input in inputs {
+            assert!(
+                matches!(Proto::parse(input), Err(ParserError::SyntaxError(_))),
+                "{} should be rejected",
+                input
+            );
+        }
+    }
+
+    // Testing reserved rule
+    #[test]
+    fn test_reserved_rule() -> Result<(), ParserError> {
+        let input = r#"
+        syntax = "proto3";
+        message Retired {
+            reserved 2, 15, 9 to 11;
+            reserved 100 to max;
+            reserved "foo", "bar";
+            string name = 1;
+        }
+        enum Color {
+            reserved 5, 10 to max;
+            reserved "PURPLE";
+            COLOR_UNKNOWN = 0;
+        }
+    "#;
+        let json = Proto::parse(input)?.to_json()?;
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        let message = &value["messages"][0];
+        let ranges = &message["reserved_ranges"];
+        assert_eq!(ranges[0]["start"], 2);
+        assert_eq!(ranges[0]["end"], 2);
+        assert_eq!(ranges[1]["start"], 15);
+        assert_eq!(ranges[1]["end"], 15);
+        assert_eq!(ranges[2]["start"], 9);
+        assert_eq!(ranges[2]["end"], 11);
+        assert_eq!(ranges[3]["start"], 100);
+        assert_eq!(ranges[3]["end"], 536870911);
+        assert_eq!(message["reserved_names"][0], "foo");
+        assert_eq!(message["reserved_names"][1], "bar");
+
+        let enum_def = &value["enums"][0];
+        assert_eq!(enum_def["reserved_ranges"][0]["start"], 5);
+        assert_eq!(enum_def["reserved_ranges"][1]["start"], 10);
+        assert_eq!(enum_def["reserved_ranges"][1]["end"], 2147483647);
+        assert_eq!(enum_def["reserved_names"][0], "PURPLE");
+
+        Ok(())
+    }
+
+    // Testing reserved range validation
+    #[test]
+    fn test_reserved_invalid_range() {
+        let input = "message M { reserved 11 to 9; }";
+        assert!(matches!(
+            Proto::parse(input),
+            Err(ParserError::SyntaxError(_))
+        ));
+    }
+
+    // Testing extensions and extend_def rules
+    #[test]
+    fn test_extend_def_rule() -> Result<(), ParserError> {
+        let input = r#"
+        syntax = "proto2";
+        package acme.v1;
+
+        message Foo {
+            extensions 100 to 199;
+            extensions 500, 1000 to max [(acme.verified) = true];
+            optional int32 id = 1;
+
+            extend Bar {
+                optional Foo foo_ext = 101;
+            }
+        }
+
+        extend Foo {
+            optional int32 bar = 126;
+            repeated string tags = 127;
+        }
+    "#;
+        let json = Proto::parse(input)?.to_json()?;
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        let message = &value["messages"][0];
+        let ranges = &message["extension_ranges"];
+        assert_eq!(ranges[0]["start"], 100);
+        assert_eq!(ranges[0]["end"], 199);
+        assert_eq!(ranges[1]["start"], 500);
+        assert_eq!(ranges[1]["end"], 500);
+        assert_eq!(ranges[2]["start"], 1000);
+        assert_eq!(ranges[2]["end"], 536870911);
+        assert_eq!(ranges[2]["options"][0]["name"], "(acme.verified)");
+
+        // Nested extend block
+        let nested = &message["extends"][0];
+        assert_eq!(nested["extendee"], "Bar");
+        assert_eq!(nested["scope"], "acme.v1.Foo");
+        assert_eq!(nested["fields"][0]["name"], "foo_ext");
+        assert_eq!(nested["fields"][0]["tag"], 101);
+
+        // Top-level extend block
+        let extend = &value["extends"][0];
+        assert_eq!(extend["extendee"], "Foo");
+        assert_eq!(extend["scope"], "acme.v1");
+        assert_eq!(extend["fields"][0]["name"], "bar");
+        assert_eq!(extend["fields"][0]["type_name"], "int32");
+        assert_eq!(extend["fields"][1]["name"], "tags");
+        assert_eq!(extend["fields"][1]["label"], "repeated");
+
+        Ok(())
+    }
+
+    // Testing group rule
+    #[test]
+    fn test_group_rule() -> Result<(), ParserError> {
+        let input = r#"
+        syntax = "proto2";
+        message SearchResponse {
+            repeated group Result = 1 {
+                required string url = 2;
+                optional string title = 3;
+            }
+            oneof choice {
+                group Picked = 4 {
+                    optional int32 index = 5;
+                }
+            }
+        }
+        extend SearchResponse {
+            optional group Extra = 100 {
+                optional string note = 101;
+            }
+        }
+    "#;
+        let json = Proto::parse(input)?.to_json()?;
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let message = &value["messages"][0];
+
+        // Group field refers to the synthesized message
+        let field = &message["fields"][0];
+        assert_eq!(field["name"], "result");
+        assert_eq!(field["type_name"], "Result");
+        assert_eq!(field["tag"], 1);
+        assert_eq!(field["label"], "repeated");
+        assert_eq!(field["kind"], "group");
+
+        let nested = &message["nested_messages"];
+        assert_eq!(nested[0]["name"], "Result");
+        assert_eq!(nested[0]["fields"][0]["name"], "url");
+        assert_eq!(nested[0]["fields"][1]["name"], "title");
+
+        // Groups inside a oneof add their message to the enclosing message
+        assert_eq!(message["oneofs"][0]["fields"][0]["name"], "picked");
+        assert_eq!(message["oneofs"][0]["fields"][0]["kind"], "group");
+        assert_eq!(nested[1]["name"], "Picked");
+        assert_eq!(nested[1]["fields"][0]["name"], "index");
+
+        // Groups inside a top-level extend add their message to the file
+        assert_eq!(value["extends"][0]["fields"][0]["name"], "extra");
+        assert_eq!(value["messages"][1]["name"], "Extra");
+        assert_eq!(value["messages"][1]["fields"][0]["name"], "note");
+
+        Ok(())
+    }
+
+    // Testing group name validation
+    #[test]
+    fn test_group_lowercase_name() {
+        let input = "message M { optional group result = 1 { optional int32 a = 2; } }";
+        assert!(matches!(
+            Proto::parse(input),
+            Err(ParserError::SyntaxError(_))
+        ));
+    }
+
+    // Testing stream rule
+    #[test]
+    fn test_stream_rule() -> Result<(), ParserError> {
+        let input = r#"
+        syntax = "proto3";
+        service Streaming {
+            rpc Unary (Request) returns (Response);
+            rpc ClientStream (stream Request) returns (Response);
+            rpc ServerStream (Request) returns (stream Response);
+            rpc Bidi (stream Request) returns (stream Response);
+        }
+    "#;
+        let json = Proto::parse(input)?.to_json()?;
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let methods = &value["services"][0]["methods"];
+
+        let expected = [(false, false), (true, false), (false, true), (true, true)];
+        for (i, (client_streaming, server_streaming)) in expected.iter().enumerate() {
+            assert_eq!(methods[i]["input_type"], "Request");
+            assert_eq!(methods[i]["output_type"], "Response");
+            assert_eq!(methods[i]["client_streaming"], *client_streaming);
+            assert_eq!(methods[i]["server_streaming"], *server_streaming);
+        }
+
+        Ok(())
+    }
+
+    // Testing type_ref rule
+    #[test]
+    fn test_type_ref_rule() -> Result<(), ParserError> {
+        let input = r#"
+        syntax = "proto3";
+        package acme.v1;
+        message User {
+            google.protobuf.Timestamp created = 1;
+            .acme.v1.User parent = 2;
+            map<string, .acme.v1.User> friends = 3;
+            oneof owner {
+                foo.Owner relative_owner = 4;
+                .foo.Owner absolute_owner = 5;
+            }
+            option (.acme.ext).flag = true;
+        }
+        extend .google.protobuf.MessageOptions {
+            bool flag = 50000;
+        }
+        service Api {
+            rpc Get (foo.Req) returns (.foo.Resp);
+            rpc Watch (stream .foo.Req) returns (stream foo.Resp);
+        }
+    "#;
+        let json = Proto::parse(input)?.to_json()?;
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        // Relative references are kept as written, absolute ones keep the leading dot
+        let message = &value["messages"][0];
+        assert_eq!(message["fields"][0]["type_name"], "google.protobuf.Timestamp");
+        assert_eq!(message["fields"][1]["type_name"], ".acme.v1.User");
+        assert_eq!(message["fields"][2]["kind"]["map"]["value_type"], ".acme.v1.User");
+        assert_eq!(message["oneofs"][0]["fields"][0]["type_name"], "foo.Owner");
+        assert_eq!(message["oneofs"][0]["fields"][1]["type_name"], ".foo.Owner");
+        assert_eq!(message["options"][0]["name"], "(.acme.ext).flag");
+
+        assert_eq!(value["extends"][0]["extendee"], ".google.protobuf.MessageOptions");
+
+        let methods = &value["services"][0]["methods"];
+        assert_eq!(methods[0]["input_type"], "foo.Req");
+        assert_eq!(methods[0]["output_type"], ".foo.Resp");
+        assert_eq!(methods[1]["input_type"], ".foo.Req");
+        assert_eq!(methods[1]["client_streaming"], true);
+        assert_eq!(methods[1]["output_type"], "foo.Resp");
+        assert_eq!(methods[1]["server_streaming"], true);
+
+        Ok(())
+    }
+
+    // Testing string_part rule and escape decoding
+    #[test]
+    fn test_string_part_rule() -> Result<(), ParserError> {
+        let input = r#"
+        syntax = 'proto2';
+        import 'single/quoted.proto';
+        import "split/" 'across/'
+            "literals.proto";
+        option (simple) = "line\n\ttab\\ \"quoted\" \'single\' \?";
+        option (hex_octal) = "\x41\X42\101\0";
+        option (unicode) = "é\U0001F600";
+        option (bells) = "\a\b\f\r\v";
+        option (comment_like) = "// not a comment /* either */";
+    "#;
+        let json = Proto::parse(input)?.to_json()?;
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(value["syntax"], "proto2");
+        assert_eq!(value["imports"][0]["path"], "single/quoted.proto");
+        assert_eq!(value["imports"][1]["path"], "split/across/literals.proto");
+
+        let options = &value["options"];
+        assert_eq!(
+            options[0]["value"]["string"],
+            "line\n\ttab\\ \"quoted\" 'single' ?"
+        );
+        assert_eq!(options[1]["value"]["string"], "ABA\0");
+        assert_eq!(options[2]["value"]["string"], "\u{e9}\u{1F600}");
+        assert_eq!(options[3]["value"]["string"], "\x07\x08\x0c\r\x0b");
+        assert_eq!(
+            options[4]["value"]["string"],
+            "// not a comment /* either */"
+        );
+
+        Ok(())
+    }
+
+    // Testing invalid escape sequences
+    #[test]
+    fn test_string_invalid_escapes() {
+        let escapes = [r"\q", r"\x", r"\u12", r"\U0011FFFF", r"\400"];
+        for escape in escapes {
+            let input = format!(r#"option (x) = "{}";"#, escape);
+            assert!(
+                matches!(Proto::parse(&input), Err(ParserError::SyntaxError(_))),
+                "{} should be rejected",
+                escape
+            );
+        }
+
+        // Strings that are not constants must be valid UTF-8
+        let result = Proto::parse(r#"syntax = "proto3"; import "\xff.proto";"#);
+        assert!(matches!(result, Err(ParserError::SyntaxError(_))));
+        let result = Proto::parse(
+            r#"syntax = "proto2"; message M { optional int32 a = 1 [json_name = "\377"]; }"#,
+        );
+        assert!(matches!(result, Err(ParserError::SyntaxError(_))));
+    }
+
+    // Testing string constants that are not valid UTF-8
+    #[test]
+    fn test_string_bytes() -> Result<(), ParserError> {
+        use proto_file_parser::Constant;
+
+        let input = r#"
+        syntax = "proto2";
+        message M {
+          optional bytes b = 1 [default = "\377\001"];
+          optional bytes c = 2 [default = "caf\xc3\xa9 \xfe\"q\""];
+        }
+        "#;
+        let proto = Proto::parse(input)?;
+        let fields = &proto.messages[0].fields;
+        assert_eq!(fields[0].default, Some(Constant::Bytes(vec![0xff, 0x01])));
+        assert_eq!(
+            fields[1].default,
+            Some(Constant::Bytes(b"caf\xc3\xa9 \xfe\"q\"".to_vec()))
+        );
+
+        let printed = proto.to_proto();
+        assert!(printed.contains(r#"[default = "\377\001"]"#));
+        assert!(printed.contains(r#"[default = "café \376\"q\""]"#));
+        let reparsed = Proto::parse(&printed)?;
+        assert_eq!(reparsed.without_spans(), proto.without_spans());
+        assert_eq!(reparsed.to_proto(), printed);
+        Ok(())
+    }
+
+    // Testing number, signed_number and float rules
+    #[test]
+    fn test_numeric_literals() -> Result<(), ParserError> {
+        let input = r#"
+        syntax = "proto2";
+        option (dec) = 42;
+        option (hex) = 0x1F;
+        option (oct) = 017;
+        option (neg) = -5;
+        option (pos) = +7;
+        option (big) = 18446744073709551615;
+        option (min) = -9223372036854775808;
+        option (f1) = 1.5e3;
+        option (f2) = .25;
+        option (f3) = 2.;
+        option (f4) = -1E-2;
+        option (f5) = inf;
+        option (f6) = -inf;
+        option (f7) = nan;
+        option (ident) = information;
+        message Numbers {
+            optional int32 hex_tag = 0x10;
+            optional int32 oct_tag = 017;
+            optional double d = 3 [default = -1.5];
+        }
+        enum Sign {
+            NEGATIVE = -1;
+            ZERO = 0;
+            HEX = 0x7FFFFFFF;
+            MIN = -2147483648;
+        }
+    "#;
+        let json = Proto::parse(input)?.to_json()?;
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        let options = &value["options"];
+        assert_eq!(options[0]["value"]["integer"], 42);
+        assert_eq!(options[1]["value"]["integer"], 31);
+        assert_eq!(options[2]["value"]["integer"], 15);
+        assert_eq!(options[3]["value"]["integer"], -5);
+        assert_eq!(options[4]["value"]["integer"], 7);
+        assert_eq!(options[5]["value"]["unsigned_integer"], u64::MAX);
+        assert_eq!(options[6]["value"]["integer"], i64::MIN);
+        assert_eq!(options[7]["value"]["float"], 1500.0);
+        assert_eq!(options[8]["value"]["float"], 0.25);
+        assert_eq!(options[9]["value"]["float"], 2.0);
+        assert_eq!(options[10]["value"]["float"], -0.01);
+        assert_eq!(options[11]["value"]["float"], "inf");
+        assert_eq!(options[12]["value"]["float"], "-inf");
+        assert_eq!(options[13]["value"]["float"], "nan");
+        assert_eq!(options[14]["value"]["identifier"], "information");
+
+        let fields = &value["messages"][0]["fields"];
+        assert_eq!(fields[0]["tag"], 16);
+        assert_eq!(fields[1]["tag"], 15);
+        assert_eq!(fields[2]["default"]["float"], -1.5);
+
+        let values = &value["enums"][0]["values"];
+        assert_eq!(values[0]["number"], -1);
+        assert_eq!(values[1]["number"], 0);
+        assert_eq!(values[2]["number"], 2147483647);
+        assert_eq!(values[3]["number"], -2147483648);
+
+        Ok(())
+    }
+
+    // Testing numeric overflow errors
+    #[test]
+    fn test_numeric_overflow() {
+        let inputs = [
+            "message M { int32 a = 536870912; }",
+            "message M { int32 a = 0; }",
+            "message M { int32 a = 99999999999999999999; }",
+            "enum E { A = 2147483648; }",
+            "enum E { A = -2147483649; }",
+            "option (x) = 18446744073709551616;",
+            "option (x) = -9223372036854775809;",
+            "message M { reserved 0 to 5; }",
+        ];
+        for input in inputs {
+            assert!(
+                matches!(Proto::parse(input), Err(ParserError::SyntaxError(_))),
+                "{} should be rejected",
+                input
+            );
+        }
+    }
+
+    // Testing import_modifier rule
+    #[test]
+    fn test_import_modifier_rule() -> Result<(), ParserError> {
+        let input = r#"
+        syntax = "proto3";
+        import "plain.proto";
+        import public "reexported.proto";
+        import weak "optional.proto";
+    "#;
+        let json = Proto::parse(input)?.to_json()?;
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let imports = &value["imports"];
+
+        assert_eq!(imports[0]["path"], "plain.proto");
+        assert!(imports[0]["modifier"].is_null());
+        assert_eq!(imports[1]["path"], "reexported.proto");
+        assert_eq!(imports[1]["modifier"], "public");
+        assert_eq!(imports[2]["path"], "optional.proto");
+        assert_eq!(imports[2]["modifier"], "weak");
+
+        Ok(())
+    }
+
+    // Testing edition rule and feature resolution
+    #[test]
+    fn test_edition_rule() -> Result<(), ParserError> {
+        let input = r#"
+        edition = "2023";
+        package acme;
+        option features.field_presence = IMPLICIT;
+
+        message User {
+            option features.utf8_validation = NONE;
+            string name = 1;
+            int32 age = 2 [features.field_presence = EXPLICIT];
+            repeated int32 ids = 3 [features.repeated_field_encoding = EXPANDED];
+            oneof contact {
+                string email = 4;
+            }
+        }
+
+        enum Status {
+            option features.enum_type = CLOSED;
+            STATUS_UNKNOWN = 0;
+        }
+
+        service Api {
+            rpc Get (User) returns (User);
+        }
+    "#;
+        let json = Proto::parse(input)?.to_json()?;
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(value["syntax"], "editions");
+        assert_eq!(value["edition"], "2023");
+
+        let features = &value["resolved_features"];
+
+        // Inherited from the file, with the message-level override applied
+        assert_eq!(features["acme.User.name"]["field_presence"], "IMPLICIT");
+        assert_eq!(features["acme.User.name"]["utf8_validation"], "NONE");
+        assert_eq!(features["acme.User.name"]["enum_type"], "OPEN");
+
+        // Field-level overrides
+        assert_eq!(features["acme.User.age"]["field_presence"], "EXPLICIT");
+        assert_eq!(features["acme.User.ids"]["repeated_field_encoding"], "EXPANDED");
+        assert_eq!(features["acme.User.name"]["repeated_field_encoding"], "PACKED");
+
+        // Oneof members always track presence
+        assert_eq!(features["acme.User.email"]["field_presence"], "EXPLICIT");
+
+        // Enum-level overrides are inherited by values
+        assert_eq!(features["acme.Status"]["enum_type"], "CLOSED");
+        assert_eq!(features["acme.Status.STATUS_UNKNOWN"]["enum_type"], "CLOSED");
+        assert_eq!(features["acme.Api.Get"]["field_presence"], "IMPLICIT");
+
+        Ok(())
+    }
+
+    // Testing feature resolution of proto2 and proto3 files
+    #[test]
+    fn test_legacy_features() -> Result<(), ParserError> {
+        let proto2 = r#"
+        syntax = "proto2";
+        message Legacy {
+            required string id = 1;
+            optional string name = 2;
+            repeated int32 ids = 3 [packed = true];
+        }
+        enum Kind { KIND_UNKNOWN = 0; }
+    "#;
+        let value: serde_json::Value = serde_json::from_str(&Proto::parse(proto2)?.to_json()?).unwrap();
+        let features = &value["resolved_features"];
+        assert_eq!(features["Legacy.id"]["field_presence"], "LEGACY_REQUIRED");
+        assert_eq!(features["Legacy.name"]["field_presence"], "EXPLICIT");
+        assert_eq!(features["Legacy.ids"]["repeated_field_encoding"], "PACKED");
+        assert_eq!(features["Legacy.name"]["utf8_validation"], "NONE");
+        assert_eq!(features["Kind"]["enum_type"], "CLOSED");
+
+        let proto3 = r#"
+        syntax = "proto3";
+        message Modern {
+            string id = 1;
+            optional string name = 2;
+        }
+    "#;
+        let value: serde_json::Value = serde_json::from_str(&Proto::parse(proto3)?.to_json()?).unwrap();
+        let features = &value["resolved_features"];
+        assert_eq!(features["Modern.id"]["field_presence"], "IMPLICIT");
+        assert_eq!(features["Modern.name"]["field_presence"], "EXPLICIT");
+
+        Ok(())
+    }
+
+    // Testing edition and feature validation
+    #[test]
+    fn test_edition_invalid() {
+        let inputs = [
+            r#"edition = "1999";"#,
+            r#"edition = "2023"; option features.field_presence = SOMETIMES;"#,
+            r#"edition = "2023"; option features.unknown_feature = OPEN;"#,
+        ];
+        for input in inputs {
+            assert!(
+                matches!(Proto::parse(input), Err(ParserError::ValidationError(_))),
+                "{} should be rejected",
+                input
+            );
+        }
+    }
+
+    // Testing keywords used as identifiers
+    #[test]
+    fn test_keywords_as_identifiers() -> Result<(), ParserError> {
+        let keywords = [
+            "syntax",
+            "edition",
+            "package",
+            "import",
+            "public",
+            "weak",
+            "option",
+            "message",
+            "enum",
+            "service",
+            "rpc",
+            "returns",
+            "stream",
+            "oneof",
+            "map",
+            "group",
+            "reserved",
+            "extensions",
+            "extend",
+            "to",
+            "max",
+            "optional",
+            "required",
+            "repeated",
+            "true",
+            "false",
+            "inf",
+            "nan",
+            "double",
+            "float",
+            "int32",
+            "int64",
+            "uint32",
+            "uint64",
+            "sint32",
+            "sint64",
+            "fixed32",
+            "fixed64",
+            "sfixed32",
+            "sfixed64",
+            "bool",
+            "string",
+            "bytes",
+        ];
+        for keyword in keywords {
+            let input = format!(
+                r#"
+                syntax = "proto2";
+                message {kw} {{
+                    optional {kw} {kw} = 1;
+                    oneof {kw}_choice {{
+                        string {kw}_value = 2;
+                    }}
+                    enum {kw}_enum {{
+                        {kw} = 0;
+                    }}
+                    enum {kw} {{
+                        {kw} = 0;
+                    }}
+                }}
+                enum {kw} {{
+                    {kw} = 0;
+                }}
+                service {kw} {{
+                    rpc {kw} ({kw}) returns ({kw});
+                }}
+                "#,
+                kw = keyword
+            );
+            let json = Proto::parse(&input)?.to_json()?;
+            let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+            let message = &value["messages"][0];
+            assert_eq!(message["name"], keyword);
+            assert_eq!(message["fields"][0]["name"], keyword);
+            assert_eq!(message["fields"][0]["type_name"], keyword);
+            assert_eq!(message["fields"][0]["label"], "optional");
+            assert_eq!(
+                message["oneofs"][0]["fields"][0]["name"],
+                format!("{}_value", keyword)
+            );
+            assert_eq!(message["nested_enums"][0]["values"][0]["name"], keyword);
+            assert_eq!(message["nested_enums"][1]["name"], keyword);
+            assert_eq!(message["nested_enums"][1]["values"][0]["name"], keyword);
+            assert_eq!(value["enums"][0]["name"], keyword);
+            assert_eq!(value["enums"][0]["values"][0]["name"], keyword);
+            assert!(message["options"].as_array().unwrap().is_empty());
+
+            let method = &value["services"][0]["methods"][0];
+            assert_eq!(value["services"][0]["name"], keyword);
+            assert_eq!(method["name"], keyword);
+            assert_eq!(method["input_type"], keyword);
+            assert_eq!(method["output_type"], keyword);
+            assert_eq!(method["client_streaming"], false);
+        }
+        Ok(())
+    }
+
+    // Testing identifiers that start with a keyword
+    #[test]
+    fn test_keyword_prefixed_identifiers() -> Result<(), ParserError> {
+        let input = r#"
+        syntax = "proto3";
+        message messageFoo {
+            stringValue string_value = 1;
+            int32Value int32_value = 2;
+            optionalThing optional_thing = 3;
+            repeatedThing repeated_thing = 4;
+            map<string, bytesValue> values = 5;
+            option.Foo option_foo = 6;
+        }
+        enum Keys {
+            optionA = 0;
+            reservedB = 1;
+        }
+        service Api {
+            rpc Get (streamFoo) returns (stream stream);
+            rpc List (stream.Foo) returns (returnsFoo);
+        }
+    "#;
+        let json = Proto::parse(input)?.to_json()?;
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        let message = &value["messages"][0];
+        assert_eq!(message["name"], "messageFoo");
+        assert_eq!(message["fields"][0]["type_name"], "stringValue");
+        assert_eq!(message["fields"][1]["type_name"], "int32Value");
+        assert_eq!(message["fields"][2]["type_name"], "optionalThing");
+        assert_eq!(message["fields"][2]["label"], "implicit");
+        assert_eq!(message["fields"][3]["type_name"], "repeatedThing");
+        assert_eq!(message["fields"][3]["label"], "implicit");
+        assert_eq!(
+            message["fields"][4]["kind"]["map"]["value_type"],
+            "bytesValue"
+        );
+        assert_eq!(message["fields"][5]["type_name"], "option.Foo");
+
+        let values = &value["enums"][0]["values"];
+        assert_eq!(values[0]["name"], "optionA");
+        assert_eq!(values[1]["name"], "reservedB");
+        assert!(value["enums"][0]["options"].as_array().unwrap().is_empty());
+
+        let methods = &value["services"][0]["methods"];
+        assert_eq!(methods[0]["input_type"], "streamFoo");
+        assert_eq!(methods[0]["client_streaming"], false);
+        assert_eq!(methods[0]["output_type"], "stream");
+        assert_eq!(methods[0]["server_streaming"], true);
+        assert_eq!(methods[1]["input_type"], "stream.Foo");
+        assert_eq!(methods[1]["client_streaming"], false);
+        assert_eq!(methods[1]["output_type"], "returnsFoo");
+
+        // A keyword glued to the following identifier does not start a statement
+        let inputs = [
+            "messageFoo {}",
+            "syntax = \"proto3\"; enumFoo { A = 0; }",
+            "packagefoo;",
+            "service Api { rpcGet (A) returns (B); }",
+            "service Api { rpc Get (A) returnsFoo (B); }",
+        ];
+        for input in inputs {
+            assert!(
+                matches!(Proto::parse(input), Err(ParserError::ParseError(_))),
+                "{} should be rejected",
+                input
+            );
+        }
+        Ok(())
+    }
+
+    // Testing parse errors of misspelled keywords
+    #[test]
+    fn test_keyword_errors() {
+        let error = Proto::parse("syntax = \"proto3\";\nmesage Foo {}").unwrap_err();
+        let message = error.to_string();
+        assert!(message.contains(" --> 2:1"), "{}", message);
+        assert!(
+            message.contains(
+                "expected EOI, syntax, edition, package, import, message_def, option_stmt, \
+                 extend_def, enum_def, or service_def"
+            ),
+            "{}",
+            message
+        );
+
+        let error = Proto::parse("service Api { rpc Get (A) return (B); }").unwrap_err();
+        assert!(error.to_string().contains("expected returns"), "{}", error);
+        assert!(!error.to_string().contains("kw_"), "{}", error);
+    }
+
+    // Testing negative enum values and allow_alias
+    #[test]
+    fn test_enum_alias() -> Result<(), ParserError> {
+        let input = r#"
+        syntax = "proto3";
+        enum Status {
+            option allow_alias = true;
+            UNKNOWN = -1;
+            STARTED = 1;
+            RUNNING = 1;
+            MIN = -2147483648;
+            LOWEST = -0x80000000;
+        }
+    "#;
+        let json = Proto::parse(input)?.to_json()?;
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        let enum_def = &value["enums"][0];
+        assert_eq!(enum_def["values"][0]["number"], -1);
+        assert_eq!(enum_def["values"][3]["number"], i32::MIN);
+
+        let aliases = &enum_def["aliases"];
+        assert_eq!(aliases.as_array().unwrap().len(), 2);
+        assert_eq!(aliases[0]["name"], "RUNNING");
+        assert_eq!(aliases[0]["target"], "STARTED");
+        assert_eq!(aliases[0]["number"], 1);
+        assert_eq!(aliases[1]["name"], "LOWEST");
+        assert_eq!(aliases[1]["target"], "MIN");
+
+        // Duplicated numbers are rejected unless aliasing is enabled
+        let inputs = [
+            "enum E { A = 0; B = 0; }",
+            "enum E { option allow_alias = false; A = 0; B = 0; }",
+            "message M { enum E { A = -1; B = -1; } }",
+        ];
+        for input in inputs {
+            assert!(
+                matches!(Proto::parse(input), Err(ParserError::ValidationError(_))),
+                "{} should be rejected",
+                input
+            );
+        }
+        assert!(Proto::parse("enum E { A = -2147483649; }").is_err());
+
+        let error = Proto::parse("enum E {\n  A = 0;\n  B = 0;\n}").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Validation error: 'B' uses the same number 0 as 'A' in enum 'E', \
+             set 'option allow_alias = true;' to allow aliases at line 3, column 3"
+        );
+        Ok(())
+    }
+
+    // Testing the typed AST returned by the parser
+    #[test]
+    fn test_typed_ast() -> Result<(), ParserError> {
+        use proto_file_parser::{Constant, FieldKind, ImportModifier, Label};
+
+        let input = r#"
+        syntax = "proto3";
+        package acme.v1;
+        import public "other.proto";
+        option java_package = "com.acme.v1";
+        message User {
+            optional string name = 1 [deprecated = true];
+            map<string, int64> counts = 2;
+            enum Role { ROLE_UNKNOWN = 0; }
+        }
+        service Users {
+            rpc Watch (stream User) returns (User);
+        }
+    "#;
+        let proto = Proto::parse(input)?;
+
+        assert_eq!(proto.syntax, "proto3");
+        assert_eq!(proto.package.as_deref(), Some("acme.v1"));
+        assert_eq!(proto.imports[0].path, "other.proto");
+        assert_eq!(proto.imports[0].modifier, Some(ImportModifier::Public));
+        assert_eq!(proto.options[0].name, "java_package");
+        assert!(matches!(&proto.options[0].value, Constant::String(value) if value == "com.acme.v1"));
+
+        let user = &proto.messages[0];
+        assert_eq!(user.name, "User");
+        assert_eq!(user.fields[0].label, Label::Optional);
+        assert_eq!(user.fields[0].deprecated, Some(true));
+        assert!(matches!(
+            &user.fields[1].kind,
+            FieldKind::Map { key_type, value_type } if key_type == "string" && value_type == "int64"
+        ));
+        assert_eq!(user.nested_enums[0].values[0].name, "ROLE_UNKNOWN");
+
+        let method = &proto.services[0].methods[0];
+        assert_eq!(method.input_type, "User");
+        assert!(method.client_streaming);
+        assert!(!method.server_streaming);
+
+        // JSON rendering is a separate step
+        let value: serde_json::Value = serde_json::from_str(&proto.to_json()?).unwrap();
+        assert_eq!(value["messages"][0]["name"], "User");
+        assert_eq!(value["resolved_features"]["acme.v1.User.name"]["field_presence"], "EXPLICIT");
+        Ok(())
+    }
+
+    // Testing that the AST round-trips through JSON
+    #[test]
+    fn test_json_roundtrip() -> Result<(), ParserError> {
+        use proto_file_parser::Constant;
+
+        let input = r#"
+        syntax = "proto2";
+        package acme.v1;
+        import weak "other.proto";
+        option (acme.limits) = { max: inf min: -inf ratio: 0.5 tags: ["a", "b"] [acme.ext] { on: true } };
+        message User {
+            option deprecated = true;
+            required string name = 1 [default = "anon", json_name = "userName"];
+            optional double score = 2 [default = -1.5e3];
+            optional uint64 big = 3 [default = 18446744073709551615];
+            optional double missing = 10 [default = nan, (lower) = -inf, (upper) = inf];
+            map<string, .acme.v1.User> friends = 4;
+            repeated group Result = 5 {
+                optional string url = 6;
+            }
+            oneof contact {
+                string email = 7;
+                string phone = 8;
+            }
+            extensions 100 to max [verification = UNVERIFIED];
+            reserved 9, 15 to 20;
+            reserved "old";
+            extend Other { optional int32 tag = 100; }
+        }
+        enum Status {
+            option allow_alias = true;
+            UNKNOWN = -1;
+            STARTED = 1 [deprecated = true];
+            RUNNING = 1;
+        }
+        service Users {
+            rpc Watch (stream User) returns (stream User) {
+                option idempotency_level = NO_SIDE_EFFECTS;
+            }
+        }
+    "#;
+        let proto = Proto::parse(input)?;
+        let json = proto.to_json()?;
+        let loaded = Proto::from_json(&json)?;
+
+        assert_eq!(loaded.without_spans(), proto.without_spans());
+        assert_eq!(loaded.to_json()?, json);
+
+        // Non-finite floats survive the round trip and compare equal
+        let missing = &loaded.messages[0].fields[3];
+        assert!(matches!(missing.default, Some(Constant::Float(value)) if value.is_nan()));
+        assert_eq!(missing.options[0].value, Constant::Float(f64::NEG_INFINITY));
+        assert_eq!(missing.options[1].value, Constant::Float(f64::INFINITY));
+        assert_ne!(missing.options[0].value, missing.options[1].value);
+        assert_eq!(proto, proto.clone());
+        assert!(loaded.eq_ignoring_spans(&proto));
+
+        assert!(matches!(
+            Proto::from_json(r#"{"syntax": 3}"#),
+            Err(ParserError::SerializationError(_))
+        ));
+        Ok(())
+    }
+
+    // Testing source spans of definitions
+    #[test]
+    fn test_spans() -> Result<(), ParserError> {
+        let message = "message User {
+  string name = 1 [deprecated = true];
+  enum Role {
+    ROLE_UNKNOWN = 0;
+  }
+}";
+        let input = format!(
+            "syntax = \"proto3\";\n{}\nservice Users {{\n  rpc Get (User) returns (User);\n}}\n",
+            message
+        );
+        let input = input.as_str();
+        let proto = Proto::parse_with_file_id(input, 7)?;
+
+        let user = &proto.messages[0];
+        assert_eq!(&input[user.span.start..user.span.end], message);
+        assert_eq!((user.span.start_line, user.span.start_column), (2, 1));
+        assert_eq!((user.span.end_line, user.span.end_column), (7, 2));
+        assert_eq!(user.span.file_id, 7);
+
+        let field = &user.fields[0];
+        assert_eq!(
+            &input[field.span.start..field.span.end],
+            "string name = 1 [deprecated = true];"
+        );
+        assert_eq!((field.span.start_line, field.span.start_column), (3, 3));
+        assert_eq!((field.span.end_line, field.span.end_column), (3, 39));
+
+        let value = &user.nested_enums[0].values[0];
+        assert_eq!(&input[value.span.start..value.span.end], "ROLE_UNKNOWN = 0;");
+        assert_eq!(value.span.file_id, 7);
+
+        let method = &proto.services[0].methods[0];
+        assert_eq!((method.span.start_line, method.span.start_column), (9, 3));
+
+        // Spans are only part of the JSON output when requested
+        let json: serde_json::Value = serde_json::from_str(&proto.to_json()?).unwrap();
+        assert!(json["messages"][0].get("span").is_none());
+        let json_with_spans = proto.to_json_with_spans()?;
+        let value: serde_json::Value = serde_json::from_str(&json_with_spans).unwrap();
+        assert_eq!(value["messages"][0]["fields"][0]["span"]["start_line"], 3);
+        assert_eq!(value["services"][0]["methods"][0]["span"]["file_id"], 7);
+
+        // Spans survive a JSON round trip and can be ignored when comparing definitions
+        let loaded = Proto::from_json(&json_with_spans)?;
+        assert_eq!(loaded.messages[0].fields[0].span.start, field.span.start);
+        assert_eq!(loaded, proto);
+        let moved = Proto::parse(&format!("\n\n{}", input))?;
+        assert_eq!(moved.messages[0].span.start_line, 4);
+        assert_ne!(moved.messages[0].span, proto.messages[0].span);
+        assert_ne!(moved, proto);
+        assert!(moved.eq_ignoring_spans(&proto));
+        Ok(())
+    }
+
+    // Testing comments attached to definitions
+    #[test]
+    fn test_comments() -> Result<(), ParserError> {
+        let input = r#"
+syntax = "proto2";
+
+// Detached comment for Foo
+
+/** Leading comment for Foo */
+message Foo {  // Trailing comment for Foo
+  optional int32 foo = 1;  // Comment attached to foo.
+  // Comment attached to bar.
+  optional int32 bar = 2;
+
+  optional string baz = 3;
+  // Comment attached to baz.
+  // Another line attached to baz.
+
+  // Comment attached to moo.
+  //
+  // Another line attached to moo.
+  optional double moo = 4;
+
+  // Detached comment for corge.
+
+  optional string corge = 5 [default = "// not a comment"];
+  /* Block comment attached
+   * to corge.  Leading asterisks
+   * will be removed. */
+  /* Block comment attached to
+   * grault. */
+  optional int32 grault = 6;
+
+  // Ignored detached comment.
+}
+
+enum Status {
+  // Unknown status
+  STATUS_UNKNOWN = 0; // Default value
+}
+
+// Users API
+service Users {
+  // Gets a user
+  rpc Get (Foo) returns (Foo); // Unary
+  rpc Watch (Foo) returns (stream Foo) { // Streaming
+    // Not idempotent
+    option idempotency_level = NO_SIDE_EFFECTS;
+  }
+}
+"#;
+        let json = Proto::parse(input)?.to_json()?;
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        let message = &value["messages"][0];
+        assert_eq!(message["comments"]["leading"], "* Leading comment for Foo ");
+        assert_eq!(message["comments"]["trailing"], " Trailing comment for Foo\n");
+        assert_eq!(message["comments"]["leading_detached"][0], " Detached comment for Foo\n");
+
+        let fields = &message["fields"];
+        assert_eq!(fields[0]["comments"]["trailing"], " Comment attached to foo.\n");
+        assert_eq!(fields[1]["comments"]["leading"], " Comment attached to bar.\n");
+        assert_eq!(
+            fields[2]["comments"]["trailing"],
+            " Comment attached to baz.\n Another line attached to baz.\n"
+        );
+        assert_eq!(
+            fields[3]["comments"]["leading"],
+            " Comment attached to moo.\n\n Another line attached to moo.\n"
+        );
+        assert!(fields[3]["comments"]["trailing"].is_null());
+        assert_eq!(fields[4]["comments"]["leading_detached"][0], " Detached comment for corge.\n");
+        assert!(fields[4]["comments"]["leading"].is_null());
+        assert_eq!(
+            fields[4]["comments"]["trailing"],
+            " Block comment attached\n to corge.  Leading asterisks\n will be removed. "
+        );
+        assert_eq!(fields[4]["default"]["string"], "// not a comment");
+        assert_eq!(fields[5]["comments"]["leading"], " Block comment attached to\n grault. ");
+        assert!(fields[5]["comments"]["trailing"].is_null());
+
+        let enum_value = &value["enums"][0]["values"][0];
+        assert_eq!(enum_value["comments"]["leading"], " Unknown status\n");
+        assert_eq!(enum_value["comments"]["trailing"], " Default value\n");
+
+        let service = &value["services"][0];
+        assert_eq!(service["comments"]["leading"], " Users API\n");
+        let methods = &service["methods"];
+        assert_eq!(methods[0]["comments"]["leading"], " Gets a user\n");
+        assert_eq!(methods[0]["comments"]["trailing"], " Unary\n");
+        assert!(methods[1]["comments"]["leading"].is_null());
+        assert_eq!(methods[1]["comments"]["trailing"], " Streaming\n");
+        assert_eq!(methods[1]["options"][0]["comments"]["leading"], " Not idempotent\n");
+
+        // Definitions without comments have no comments entry
+        assert!(value["enums"][0].get("comments").is_none());
+        Ok(())
+    }
+
+    // Testing comments with non-ASCII text
+    #[test]
+    fn test_comments_non_ascii() -> Result<(), ParserError> {
+        let input = "syntax = \"proto3\";\n/* café */\nmessage M {\n  string name = 1; /* naïve — ünïcode */\n}\n";
+        let proto = Proto::parse(input)?;
+        assert_eq!(proto.messages[0].comments.leading.as_deref(), Some(" café "));
+        assert_eq!(
+            proto.messages[0].fields[0].comments.trailing.as_deref(),
+            Some(" naïve — ünïcode ")
+        );
+        Ok(())
+    }
+
+    // Testing comments in a large file
+    #[test]
+    fn test_comments_large_file() -> Result<(), ParserError> {
+        let mut input = String::from("syntax = \"proto3\";\nmessage Big {\n");
+        for i in 1..=20000 {
+            input += &format!("  // Leading f{i}\n  int32 f{i} = {i}; // Trailing f{i}\n");
+        }
+        input += "}\n";
+
+        let proto = Proto::parse(&input)?;
+        let fields = &proto.messages[0].fields;
+        assert_eq!(fields.len(), 20000);
+        for i in [0, 9999, 19999] {
+            let n = i + 1;
+            assert_eq!(fields[i].comments.leading, Some(format!(" Leading f{}\n", n)));
+            assert_eq!(fields[i].comments.trailing, Some(format!(" Trailing f{}\n", n)));
+        }
+        Ok(())
+    }
+
+    // Testing declaration order of definitions
+    #[test]
+    fn test_declaration_order() -> Result<(), ParserError> {
+        use proto_file_parser::DeclarationKind;
+
+        let input = r#"
+        syntax = "proto2";
+        enum First { A = 0; }
+        message Second {
+            enum Inner { B = 0; }
+            optional int32 a = 1;
+            message Nested {}
+            oneof choice {
+                string b = 2;
+                group Choice = 3 {}
+            }
+            optional group Result = 4 {}
+            extensions 100 to 199;
+            extend Second { optional int32 c = 100; }
+            optional int32 d = 5;
+        }
+        service Third {}
+        enum Fourth { C = 0; }
+        extend Second { optional int32 e = 101; }
+        "#;
+
+        let proto = Proto::parse(input)?;
+        let order: Vec<_> = proto
+            .declarations
+            .iter()
+            .map(|declaration| (declaration.kind, declaration.index))
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                (DeclarationKind::Enum, 0),
+                (DeclarationKind::Message, 0),
+                (DeclarationKind::Service, 0),
+                (DeclarationKind::Enum, 1),
+                (DeclarationKind::Extend, 0),
+            ]
+        );
+        assert_eq!(proto.enums[1].name, "Fourth");
+
+        let message = &proto.messages[0];
+        let order: Vec<_> = message
+            .declarations
+            .iter()
+            .map(|declaration| (declaration.kind, declaration.index))
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                (DeclarationKind::Enum, 0),
+                (DeclarationKind::Field, 0),
+                (DeclarationKind::Message, 0),
+                (DeclarationKind::Oneof, 0),
+                (DeclarationKind::Message, 1),
+                (DeclarationKind::Field, 1),
+                (DeclarationKind::Message, 2),
+                (DeclarationKind::Extend, 0),
+                (DeclarationKind::Field, 2),
+            ]
+        );
+        assert_eq!(message.nested_messages[1].name, "Choice");
+        assert_eq!(message.nested_messages[2].name, "Result");
+        assert_eq!(message.fields[2].name, "d");
+
+        // The order is part of the JSON output
+        let json = proto.to_json()?;
+        assert!(json.contains("\"kind\": \"service\""));
+        assert_eq!(Proto::from_json(&json)?, proto.without_spans());
+        Ok(())
+    }
+
+    // Testing Visit and VisitMut traversal
+    #[test]
+    fn test_visitors() -> Result<(), ParserError> {
+        use proto_file_parser::visit::{self, Visit, VisitMut};
+        use proto_file_parser::{EnumDef, EnumValue, Field, Message, Method, Service};
+
+        let input = r#"
+        syntax = "proto2";
+        package acme.v1;
+        message User {
+            optional string name = 1;
+            oneof contact {
+                string email = 2;
+            }
+            message Address {
+                optional string city = 1;
+            }
+            enum Role { ROLE_UNKNOWN = 0; }
+            extend Other { optional int32 user_ext = 100; }
+        }
+        enum Status { ACTIVE = 0; }
+        extend Other { optional int32 file_ext = 101; }
+        service Users {
+            rpc Get (User) returns (User);
+        }
+        "#;
+        let mut proto = Proto::parse(input)?;
+
+        struct Names(Vec<String>);
+
+        impl Visit for Names {
+            fn visit_message(&mut self, message: &Message, scope: &str) {
+                self.0.push(format!("message {} in {}", message.name, scope));
+                visit::walk_message(self, message, scope);
+            }
+            fn visit_field(&mut self, field: &Field, scope: &str) {
+                self.0.push(format!("field {} in {}", field.name, scope));
+            }
+            fn visit_enum(&mut self, enum_def: &EnumDef, scope: &str) {
+                self.0.push(format!("enum {} in {}", enum_def.name, scope));
+                visit::walk_enum(self, enum_def, scope);
+            }
+            fn visit_enum_value(&mut self, value: &EnumValue, scope: &str) {
+                self.0.push(format!("value {} in {}", value.name, scope));
+            }
+            fn visit_service(&mut self, service: &Service, scope: &str) {
+                self.0.push(format!("service {} in {}", service.name, scope));
+                visit::walk_service(self, service, scope);
+            }
+            fn visit_method(&mut self, method: &Method, scope: &str) {
+                self.0.push(format!("method {} in {}", method.name, scope));
+            }
+        }
+
+        let mut names = Names(Vec::new());
+        names.visit_proto(&proto);
+        assert_eq!(
+            names.0,
+            [
+                "message User in acme.v1",
+                "field name in acme.v1.User",
+                "field email in acme.v1.User",
+                "message Address in acme.v1.User",
+                "field city in acme.v1.User.Address",
+                "enum Role in acme.v1.User",
+                "value ROLE_UNKNOWN in acme.v1.User.Role",
+                "field user_ext in acme.v1.User",
+                "enum Status in acme.v1",
+                "value ACTIVE in acme.v1.Status",
+                "field file_ext in acme.v1",
+                "service Users in acme.v1",
+                "method Get in acme.v1.Users",
+            ]
+        );
+
+        // Not overriding a callback keeps the default walk, so nested fields are still reached
+        struct Deprecate;
+
+        impl VisitMut for Deprecate {
+            fn visit_field_mut(&mut self, field: &mut Field, scope: &str) {
+                if scope.ends_with("Address") {
+                    field.deprecated = Some(true);
+                }
+            }
+        }
+
+        Deprecate.visit_proto_mut(&mut proto);
+        assert_eq!(proto.messages[0].nested_messages[0].fields[0].deprecated, Some(true));
+        assert_eq!(proto.messages[0].fields[0].deprecated, None);
+        Ok(())
+    }
+
+    // Testing schemas built in code
+    #[test]
+    fn test_builder() -> Result<(), ParserError> {
+        use proto_file_parser::{Comments, Constant, ImportModifier, Label};
+
+        let input = r#"
+        syntax = "proto2";
+        package acme.v1;
+        import public "other.proto";
+        option java_package = "com.acme";
+        // Status of a user
+        enum Status {
+            option allow_alias = true;
+            ACTIVE = 0;
+            ENABLED = 0 [deprecated = true];
+            reserved 5 to 9;
+        }
+        message User {
+            required string name = 1 [json_name = "userName"];
+            map<string, int32> scores = 2;
+            oneof contact {
+                string email = 3;
+                string phone = 4;
+            }
+            optional group Result = 5 {
+                optional string url = 6;
+            }
+            message Address {}
+            extensions 100 to 199;
+            reserved 20, 30 to 40;
+            reserved "old";
+        }
+        extend User { optional int32 level = 100; }
+        service Users {
+            rpc Watch (stream User) returns (stream Status) {
+                option deprecated = true;
+            }
+        }
+        "#;
+        let parsed = Proto::parse(input)?;
+
+        let built = Proto::builder()
+            .syntax("proto2")
+            .package("acme.v1")
+            .import_with("other.proto", ImportModifier::Public)
+            .option("java_package", Constant::String("com.acme".to_string()))
+            .enum_def("Status", |e| {
+                e.comments(Comments {
+                    leading: Some(" Status of a user\n".to_string()),
+                    ..Comments::default()
+                })
+                .option("allow_alias", Constant::Bool(true))
+                .value("ACTIVE", 0)
+                .value_with("ENABLED", 0, |v| v.option("deprecated", Constant::Bool(true)))
+                .reserved_range(5, 9)
+            })
+            .message("User", |m| {
+                m.field_with("name", "string", 1, |f| {
+                    f.label(Label::Required)
+                        .option("json_name", Constant::String("userName".to_string()))
+                })
+                .map_field("scores", "string", "int32", 2)
+                .oneof("contact", |o| o.field("email", "string", 3).field("phone", "string", 4))
+                .group(Label::Optional, "Result", 5, |g| {
+                    g.field_with("url", "string", 6, |f| f.label(Label::Optional))
+                })
+                .message("Address", |a| a)
+                .extension_range(100, 199)
+                .reserved_range(20, 20)
+                .reserved_range(30, 40)
+                .reserved_name("old")
+            })
+            .extend("User", |x| x.field_with("level", "int32", 100, |f| f.label(Label::Optional)))
+            .service("Users", |s| {
+                s.method_with("Watch", "User", "Status", |m| {
+                    m.client_streaming()
+                        .server_streaming()
+                        .option("deprecated", Constant::Bool(true))
+                })
+            })
+            .build()?;
+
+        assert_eq!(built, parsed.without_spans());
+        assert_eq!(built.enums[0].values[1].deprecated, Some(true));
+        assert_eq!(built.enums[0].aliases[0].target, "ACTIVE");
+        assert_eq!(built.messages[0].fields[0].json_name.as_deref(), Some("userName"));
+        assert_eq!(built.extends[0].scope, "acme.v1");
+        assert_eq!(built.to_json()?, parsed.to_json()?);
+        assert!(built.messages[0].span.is_unknown());
+        Ok(())
+    }
+
+    // Testing validation of schemas built in code
+    #[test]
+    fn test_builder_validation() {
+        use proto_file_parser::{AggregateField, Constant, Label};
+
+        let invalid = [
+            Proto::builder().syntax("proto4").build(),
+            Proto::builder().edition("2022").build(),
+            Proto::builder().package("acme..v1").build(),
+            Proto::builder().message("1User", |m| m).build(),
+            Proto::builder().message("User", |m| m.field("id", "int32", 0)).build(),
+            Proto::builder().message("User", |m| m.field("id", "in t32", 1)).build(),
+            Proto::builder()
+                .message("User", |m| m.map_field("m", "double", "string", 1))
+                .build(),
+            Proto::builder().message("User", |m| m.reserved_range(10, 5)).build(),
+            Proto::builder()
+                .message("User", |m| {
+                    m.field_with("id", "int32", 1, |f| {
+                        f.option("deprecated", Constant::String("yes".to_string()))
+                    })
+                })
+                .build(),
+            Proto::builder()
+                .enum_def("Status", |e| e.value("A", 0).value("B", 0))
+                .build(),
+            Proto::builder()
+                .service("Users", |s| s.method("Get", "User", ""))
+                .build(),
+            Proto::builder()
+                .edition("2023")
+                .option("features.field_presence", Constant::Identifier("SOMETIMES".to_string()))
+                .build(),
+            Proto::builder().option("1 2", Constant::Integer(1)).build(),
+            Proto::builder().option("(a.b", Constant::Integer(1)).build(),
+            Proto::builder().option("(a.b)x", Constant::Integer(1)).build(),
+            Proto::builder()
+                .option("x", Constant::List(vec![Constant::Integer(1)]))
+                .build(),
+            Proto::builder()
+                .option("x", Constant::Identifier("a b".to_string()))
+                .build(),
+            Proto::builder()
+                .option("x", Constant::Identifier("true".to_string()))
+                .build(),
+            Proto::builder()
+                .option("x", Constant::Identifier("nan".to_string()))
+                .build(),
+            Proto::builder()
+                .option("x", Constant::UnsignedInteger(5))
+                .build(),
+            Proto::builder()
+                .option("x", Constant::Bytes(b"abc".to_vec()))
+                .build(),
+            Proto::builder()
+                .option(
+                    "x",
+                    Constant::Aggregate(vec![AggregateField {
+                        name: "a b".to_string(),
+                        value: Constant::Integer(1),
+                    }]),
+                )
+                .build(),
+            Proto::builder()
+                .option(
+                    "x",
+                    Constant::Aggregate(vec![AggregateField {
+                        name: "a".to_string(),
+                        value: Constant::List(vec![Constant::List(Vec::new())]),
+                    }]),
+                )
+                .build(),
+            Proto::builder()
+                .message("User", |m| m.option("(bad name)", Constant::Bool(true)))
+                .build(),
+            Proto::builder()
+                .message("User", |m| {
+                    m.field_with("id", "int32", 1, |f| {
+                        f.option("(x)", Constant::Identifier("false".to_string()))
+                    })
+                })
+                .build(),
+            Proto::builder()
+                .enum_def("Status", |e| e.option("1x", Constant::Bool(true)).value("A", 0))
+                .build(),
+            Proto::builder()
+                .service("Users", |s| {
+                    s.method_with("Get", "User", "User", |m| {
+                        m.option("x", Constant::List(Vec::new()))
+                    })
+                })
+                .build(),
+            Proto::builder()
+                .message("User", |m| {
+                    m.oneof("choice", |o| {
+                        o.field_with("a", "string", 1, |f| f.label(Label::Optional))
+                    })
+                })
+                .build(),
+            Proto::builder()
+                .message("User", |m| {
+                    m.map_field_with("m", "string", "string", 1, |f| f.label(Label::Optional))
+                })
+                .build(),
+        ];
+
+        for result in invalid {
+            assert!(matches!(result, Err(ParserError::ValidationError(_))), "{:?}", result);
+        }
+
+        let error = Proto::builder()
+            .package("acme")
+            .message("User", |m| m.field("id", "int32", 0))
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Validation error: number 0 of 'acme.User.id' is out of range, expected 1 to 536870911"
+        );
+
+        let error = Proto::builder()
+            .enum_def("Status", |e| {
+                e.value_with("A", 0, |v| {
+                    v.option("deprecated", Constant::Bool(true))
+                        .option("deprecated", Constant::Bool(false))
+                })
+            })
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Validation error: option 'deprecated' is already set in 'Status.A'"
+        );
+
+        let error = Proto::builder()
+            .package("acme")
+            .message("User", |m| {
+                m.option("x", Constant::Identifier("true".to_string()))
+            })
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Validation error: invalid identifier 'true' in option 'x' of 'acme.User'"
+        );
+
+        // Valid option names and constants are accepted
+        let proto = Proto::builder()
+            .option("(acme.v1.opt).sub.(.other.ext)", Constant::UnsignedInteger(u64::MAX))
+            .option(
+                "x",
+                Constant::Aggregate(vec![
+                    AggregateField {
+                        name: "[acme.ext]".to_string(),
+                        value: Constant::List(vec![Constant::Identifier("infinity".to_string())]),
+                    },
+                    AggregateField {
+                        name: "b".to_string(),
+                        value: Constant::Bytes(vec![0xff]),
+                    },
+                ]),
+            )
+            .build()
+            .unwrap();
+        assert!(Proto::parse(&proto.to_proto()).unwrap().eq_ignoring_spans(&proto));
+    }
+
+    // Testing rendering of definitions as proto source
+    #[test]
+    fn test_to_proto() -> Result<(), ParserError> {
+        let input = r#"
+        syntax="proto2"; package acme.v1;
+        import public "other.proto";
+        // Status of a user
+        enum Status { ACTIVE=0; reserved 10 to max; }
+        message User {  // Trailing
+            required string name = 1 [json_name = "userName", (my.opt) = { a: 1 b: [2, 3] }];
+            optional string note = 2 [default = "say \"hi\"\n"];
+            map<string, int32> scores = 3;
+            optional group Result = 4 { optional string url = 5; }
+            reserved 100 to max; reserved "old";
+        }
+        service Users { rpc Watch (stream User) returns (stream Status) { option deprecated = true; } }
+        "#;
+
+        let printed = Proto::parse(input)?.to_proto();
+        let expected = r#"syntax = "proto2";
+
+package acme.v1;
+
+import public "other.proto";
+
+// Status of a user
+enum Status {
+  ACTIVE = 0;
+  reserved 10 to max;
+}
+
+message User { // Trailing
+  required string name = 1 [json_name = "userName", (my.opt) = { a: 1 b: [2, 3] }];
+  optional string note = 2 [default = "say \"hi\"\n"];
+  map<string, int32> scores = 3;
+
+  optional group Result = 4 {
+    optional string url = 5;
+  }
+
+  reserved 100 to max;
+  reserved "old";
+}
+
+service Users {
+  rpc Watch (stream User) returns (stream Status) {
+    option deprecated = true;
+  }
+}
+"#;
+        assert_eq!(printed, expected);
+        Ok(())
+    }
+
+    // Testing parse, print and parse round trips
+    #[test]
+    fn test_to_proto_roundtrip() -> Result<(), ParserError> {
+        let input = r#"
+        syntax = "proto2";
+        package acme.v1;
+        import weak "x.proto"; // Trailing import comment
+        option (my.opt).sub = { c { d: "x\ty" } [ext.e]: -inf };
+
+        // Detached comment for Foo
+
+        /** Leading comment for Foo */
+        message Foo {  // Trailing comment for Foo
+          option deprecated = true;
+          optional int32 foo = 1;  // Comment attached to foo.
+          // Comment attached to bar.
+          optional int32 bar = 2;
+
+          optional string baz = 3;
+          // Comment attached to baz.
+          // Another line attached to baz.
+
+          // Detached comment for corge.
+
+          optional string corge = 5 [default = "// not a comment \\ \001 é"];
+          /* Block comment attached
+           * to corge.  Leading asterisks
+           * will be removed. */
+          /* Block comment attached to
+           * grault. */
+          optional double grault = 6 [default = 1e20];
+          oneof choice { // Trailing comment for choice
+            string a = 8;
+            group Choice = 9 { optional int32 z = 1; }
+          }
+          message Empty {}
+          enum E {
+            option allow_alias = true;
+            A = 0;
+            B = 0 [deprecated = true, (v) = "x"];
+            C = -3;
+            reserved 5 to max, -10;
+          }
+          extend Foo { optional group G = 101 {} }
+          optional float f = 10 [default = -0.5];
+          extensions 100 to 199, 300 [declaration = { number: 1 }];
+          reserved 20, 30 to 40;
+          reserved "old";
+        }
+
+        service Users {
+          // Gets a user
+          rpc Get (Foo) returns (.acme.v1.Foo); // Unary
+        }
+        extend Foo { optional uint64 top = 102 [default = 18446744073709551615]; }
+        "#;
+
+        let proto = Proto::parse(input)?;
+        let printed = proto.to_proto();
+        let reparsed = Proto::parse(&printed)?;
+        assert_eq!(reparsed.without_spans(), proto.without_spans());
+        assert_eq!(reparsed.to_proto(), printed);
+
+        // Declaration order and comments are kept
+        let message = &reparsed.messages[0];
+        assert_eq!(message.fields[5].name, "f");
+        assert_eq!(
+            message.fields[2].comments.trailing.as_deref(),
+            Some(" Comment attached to baz.\n Another line attached to baz.\n")
+        );
+        assert_eq!(message.comments.leading_detached, [" Detached comment for Foo\n"]);
+
+        let editions = Proto::parse(r#"edition = "2023"; message M { reserved foo, bar; }"#)?;
+        assert!(editions.to_proto().contains("reserved foo, bar;"));
+        assert!(Proto::parse(&editions.to_proto())?.eq_ignoring_spans(&editions));
+        Ok(())
+    }
+
+    // Testing rendering of built and deserialized definitions
+    #[test]
+    fn test_to_proto_built() -> Result<(), ParserError> {
+        let proto = Proto::builder()
+            .package("acme.v1")
+            .enum_def("Status", |e| e.value("ACTIVE", 0))
+            .message("User", |m| {
+                m.field("status", "Status", 1)
+                    .message("Address", |a| a.field("city", "string", 1))
+            })
+            .build()?;
+
+        let printed = proto.to_proto();
+        assert!(printed.contains("enum Status {\n  ACTIVE = 0;\n}\n\nmessage User {"));
+        assert_eq!(Proto::parse(&printed)?.without_spans(), proto);
+
+        let loaded = Proto::from_json(&proto.to_json()?)?;
+        assert_eq!(loaded.to_proto(), printed);
+        Ok(())
+    }
+
+    // Testing rendering of comments containing the end of a block comment
+    #[test]
+    fn test_to_proto_comment_end() -> Result<(), ParserError> {
+        use proto_file_parser::{Comments, Constant, Label};
+
+        let proto = Proto::builder()
+            .syntax("proto2")
+            .message("User", |m| {
+                m.comments(Comments {
+                    leading: Some("a */ b".to_string()),
+                    trailing: Some(" c */".to_string()),
+                    leading_detached: vec!["d */\n e".to_string()],
+                })
+                .field_with("score", "double", 1, |f| {
+                    f.label(Label::Optional)
+                        .option("default", Constant::Float(f64::NAN))
+                        .comments(Comments {
+                            trailing: Some("*/".to_string()),
+                            ..Comments::default()
+                        })
+                })
+            })
+            .build()?;
+
+        let printed = proto.to_proto();
+        assert!(printed.contains("//a */ b\nmessage User { // c */\n"));
+        let reparsed = Proto::parse(&printed)?;
+        assert_eq!(reparsed.to_proto(), printed);
+
+        let message = &reparsed.messages[0];
+        assert_eq!(message.comments.leading.as_deref(), Some("a */ b\n"));
+        assert_eq!(message.comments.trailing.as_deref(), Some(" c */\n"));
+        assert_eq!(message.comments.leading_detached, ["d */\n e\n"]);
+        assert_eq!(message.fields[0].comments.trailing.as_deref(), Some("*/\n"));
+        assert!(matches!(message.fields[0].default, Some(Constant::Float(value)) if value.is_nan()));
+
+        // Apart from the added newlines, the reparsed definition equals the built one
+        let mut expected = proto.clone();
+        let message = &mut expected.messages[0];
+        message.comments.leading = Some("a */ b\n".to_string());
+        message.comments.trailing = Some(" c */\n".to_string());
+        message.comments.leading_detached = vec!["d */\n e\n".to_string()];
+        message.fields[0].comments.trailing = Some("*/\n".to_string());
+        assert!(reparsed.eq_ignoring_spans(&expected));
+        Ok(())
+    }
+}